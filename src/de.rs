@@ -1,6 +1,536 @@
-use std::path::Path;
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
 
-use super::error::{Error, Result};
+use serde::de::{
+    self, IntoDeserializer as _,
+    value::{SeqDeserializer, StrDeserializer},
+};
+
+use super::{
+    encoding::ByteEncoding,
+    error::{Error, Location, Result},
+    list::{Delimiter, split},
+    parse::{Interpolation, Variable, parse, parse_with},
+    prefixed::Prefixed,
+    ser::{KeyCase, NoneStyle},
+};
+
+/// Options shared by all nodes of a [`Deserializer`].
+#[derive(Debug, Clone)]
+struct Options {
+    /// Separator joining the key segments of nested structures.
+    separator: String,
+    delimiter: Delimiter,
+    key_case: KeyCase,
+    /// Whether keys are matched and yielded exactly as written instead of lowercased.
+    case_sensitive: bool,
+    none_style: NoneStyle,
+    byte_encoding: ByteEncoding,
+    /// Whether values are yielded as the type they look like to `deserialize_any`,
+    /// as done below nodes buffered by serde.
+    infer: bool,
+}
+
+impl Options {
+    /// Returns the key a field or variant name is matched against.
+    fn matched(&self, name: &str) -> String {
+        self.key(&self.key_case.apply(name))
+    }
+
+    /// Returns `name` lowercased unless keys are case-sensitive.
+    fn key(&self, name: &str) -> String {
+        match self.case_sensitive {
+            true => name.into(),
+            false => name.to_lowercase(),
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            separator: "_".into(),
+            delimiter: Delimiter::default(),
+            key_case: KeyCase::default(),
+            case_sensitive: false,
+            none_style: NoneStyle::default(),
+            byte_encoding: ByteEncoding::default(),
+            infer: false,
+        }
+    }
+}
+
+/// A single environment variable as seen by the [`Deserializer`].
+struct Entry {
+    /// Name of the variable as it appeared in the input.
+    name: String,
+    /// Name without the prefix, lowercased unless keys are case-sensitive, used
+    /// to match fields and map keys.
+    key: String,
+    value: String,
+    location: Option<Location>,
+    /// Whether the value was consumed during deserialization.
+    used: Cell<bool>,
+    /// Whether the entry stands in for a missing key while collecting errors.
+    placeholder: bool,
+    /// Whether the placeholder was read as `None` by an optional value, which
+    /// makes the key optional instead of missing.
+    none: Cell<bool>,
+    /// Whether the value was rejected by its visitor while collecting errors,
+    /// after which the entry is treated as absent.
+    rejected: bool,
+}
+
+/// Errors collected instead of aborting the deserialization at the first one.
+type Errors = RefCell<Vec<Error>>;
+
+/// A deserializer to transform environment variables into Rust data.
+///
+/// Nested structs and maps are reconstructed from keys joined with a
+/// separator (`_` unless set with [`separator`](Self::separator)), mirroring
+/// the output of the [`Serializer`](crate::Serializer). Keys are
+/// matched case-insensitively and map keys are yielded in lowercase, unless
+/// [`case_sensitive`](Self::case_sensitive) is set.
+///
+/// `&mut Deserializer` implements [`serde::Deserializer`], so that it can be
+/// used with wrappers such as `serde_path_to_error`.
+///
+/// # Example
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_envfile::{Deserializer, Error};
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Config {
+///     port: u16,
+/// }
+///
+/// fn deserializer_example() -> Result<(), Error> {
+///     let mut deserializer = Deserializer::from_str("APP_PORT=8080")?.prefix("APP_");
+///     let config = Config::deserialize(&mut deserializer)?;
+///
+///     assert_eq!(config.port, 8080);
+///     Ok(())
+/// }
+/// # deserializer_example().unwrap();
+/// ```
+pub struct Deserializer {
+    /// All variables in the order they were defined, including overridden ones.
+    variables: Vec<(String, String, Option<Location>)>,
+    /// Variables in effect that start with the prefix.
+    entries: Vec<Entry>,
+    /// Prefix of all keys as given, before the key case is applied.
+    prefix: String,
+    /// Whether keys left unused by a deserialization are an error.
+    strict: bool,
+    options: Options,
+    /// Errors of invalid values, if collected.
+    errors: Option<Errors>,
+}
+
+impl Deserializer {
+    pub(crate) fn new<Iter>(prefix: Option<&str>, iter: Iter) -> Self
+    where
+        Iter: IntoIterator<Item = (String, String)>,
+    {
+        Self::with_locations(
+            prefix,
+            iter.into_iter().map(|(name, value)| (name, value, None)),
+        )
+    }
+
+    pub(crate) fn from_variables(prefix: Option<&str>, variables: Vec<Variable>) -> Self {
+        Self::with_locations(
+            prefix,
+            variables
+                .into_iter()
+                .map(|variable| (variable.name, variable.value, Some(variable.location))),
+        )
+    }
+
+    /// Create a [`Deserializer`] from variables starting with `prefix`, which
+    /// is matched with the key case applied.
+    pub(crate) fn with_locations<Iter>(prefix: Option<&str>, iter: Iter) -> Self
+    where
+        Iter: IntoIterator<Item = (String, String, Option<Location>)>,
+    {
+        let mut deserializer = Self {
+            variables: iter.into_iter().collect(),
+            entries: Vec::new(),
+            prefix: prefix.unwrap_or_default().into(),
+            strict: false,
+            options: Options::default(),
+            errors: None,
+        };
+        deserializer.index();
+        deserializer
+    }
+
+    /// Build the entries from the variables starting with the prefix.
+    fn index(&mut self) {
+        let mut entries: Vec<Entry> = Vec::new();
+        let mut positions = HashMap::new();
+        let prefix = self.key_prefix();
+
+        for (name, value, location) in &self.variables {
+            let Some(key) = name.strip_prefix(prefix.as_str()) else {
+                continue;
+            };
+            let key = self.options.key(key);
+
+            // Later definitions of a key override earlier ones
+            match positions.get(&key) {
+                Some(&position) => {
+                    let entry: &mut Entry = &mut entries[position];
+                    entry.name = name.clone();
+                    entry.value = value.clone();
+                    entry.location = location.clone();
+                }
+                None => {
+                    positions.insert(key.clone(), entries.len());
+                    entries.push(Entry {
+                        name: name.clone(),
+                        key,
+                        value: value.clone(),
+                        location: location.clone(),
+                        used: Cell::new(false),
+                        placeholder: false,
+                        none: Cell::new(false),
+                        rejected: false,
+                    });
+                }
+            }
+        }
+
+        self.entries = entries;
+    }
+
+    /// Create a [`Deserializer`] from a string in the envfile format.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(input: &str) -> Result<Self> {
+        Self::from_str_with(input, &Interpolation::default())
+    }
+
+    /// Create a [`Deserializer`] from a string in the envfile format,
+    /// substituting variables as set by `interpolation`.
+    pub fn from_str_with(input: &str, interpolation: &Interpolation) -> Result<Self> {
+        let variables = parse_with(input, None, &mut HashMap::new(), interpolation)?;
+        Ok(Self::from_variables(None, variables))
+    }
+
+    /// Create a [`Deserializer`] from a reader of the envfile format.
+    pub fn from_reader<R: std::io::Read>(reader: R) -> Result<Self> {
+        Self::from_reader_with(reader, &Interpolation::default())
+    }
+
+    /// Create a [`Deserializer`] from a reader of the envfile format,
+    /// substituting variables as set by `interpolation`.
+    pub fn from_reader_with<R: std::io::Read>(
+        mut reader: R,
+        interpolation: &Interpolation,
+    ) -> Result<Self> {
+        let mut input = String::new();
+        reader
+            .read_to_string(&mut input)
+            .map_err(|e| Error::io(None, e))?;
+
+        Self::from_str_with(&input, interpolation)
+    }
+
+    /// Create a [`Deserializer`] from an envfile.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_file_with(path, &Interpolation::default())
+    }
+
+    /// Create a [`Deserializer`] from an envfile, substituting variables as
+    /// set by `interpolation`.
+    pub fn from_file_with<P: AsRef<Path>>(path: P, interpolation: &Interpolation) -> Result<Self> {
+        let path = path.as_ref();
+        let input = std::fs::read_to_string(path).map_err(|e| Error::io(Some(path.into()), e))?;
+        let variables = parse_with(&input, Some(path), &mut HashMap::new(), interpolation)?;
+
+        Ok(Self::from_variables(None, variables))
+    }
+
+    /// Create a [`Deserializer`] from key-value pairs.
+    pub fn from_pairs<Iter, K, V>(iter: Iter) -> Self
+    where
+        Iter: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        Self::new(None, iter.into_iter().map(|(k, v)| (k.into(), v.into())))
+    }
+
+    /// Create a [`Deserializer`] from the environment of the application.
+    pub fn from_env() -> Self {
+        Self::new(None, std::env::vars())
+    }
+
+    /// Only consider variables starting with `prefix` and strip it from their keys.
+    ///
+    /// The key case is applied to the prefix.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.into();
+        self.index();
+        self
+    }
+
+    /// Apply the prefix, separator and key case of `prefixed`, if any.
+    pub(crate) fn prefixed(self, prefixed: Option<&Prefixed>) -> Self {
+        match prefixed {
+            Some(prefixed) => self
+                .separator(prefixed.separator)
+                .key_case(prefixed.key_case)
+                .prefix(prefixed.prefix),
+            None => self,
+        }
+    }
+
+    /// Fail with [`Error::UnusedKeys`] if a deserialization leaves keys unused.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set the separator joining the key segments of nested structures, such
+    /// as `__` to tell `DATABASE__URL` apart from `DATABASE_URL`. Defaults to `_`.
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.options.separator = separator.into();
+        self
+    }
+
+    /// Set the case applied to field names and the prefix before they are
+    /// matched against keys. Defaults to [`KeyCase::Upper`].
+    ///
+    /// Unless keys are case-sensitive, only the word boundaries of the case
+    /// matter, so that [`KeyCase::ScreamingSnake`] reads `DATABASE_URL` into a field named
+    /// `databaseUrl`.
+    pub fn key_case(mut self, key_case: KeyCase) -> Self {
+        self.options.key_case = key_case;
+        self.index();
+        self
+    }
+
+    /// Match keys case-sensitively and yield map keys, such as those of
+    /// [`Value`](crate::Value), exactly as written instead of lowercased.
+    /// Defaults to `false`.
+    ///
+    /// Field names are matched after applying the key case, so combine this
+    /// with [`KeyCase::Preserve`] to match them as they are named in Rust.
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.options.case_sensitive = case_sensitive;
+        self.index();
+        self
+    }
+
+    /// Set the delimiter between the elements of sequences stored in a single
+    /// variable. Defaults to [`Delimiter::Comma`].
+    pub fn delimiter(mut self, delimiter: Delimiter) -> Self {
+        self.options.delimiter = delimiter;
+        self
+    }
+
+    /// Set which values are read as `None`. Defaults to [`NoneStyle::Empty`],
+    /// reading empty values as `None`.
+    pub fn none_style(mut self, none_style: NoneStyle) -> Self {
+        self.options.none_style = none_style;
+        self
+    }
+
+    /// Set the encoding of byte strings. Defaults to [`ByteEncoding::Utf8`].
+    pub fn byte_encoding(mut self, byte_encoding: ByteEncoding) -> Self {
+        self.options.byte_encoding = byte_encoding;
+        self
+    }
+
+    /// Returns the names of the variables that no deserialization consumed so far.
+    ///
+    /// This includes keys that no field matched, such as typos, as well as
+    /// keys of fields that were skipped.
+    pub fn unused_keys(&self) -> Vec<&str> {
+        self.entries
+            .iter()
+            .filter(|entry| !entry.used.get() && !entry.placeholder)
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    /// Returns the prefix with the key case applied, as it starts keys.
+    fn key_prefix(&self) -> String {
+        self.options.key_case.apply(&self.prefix)
+    }
+
+    /// Check for unused keys after the root was deserialized in strict mode.
+    fn finish<T>(&self, value: Result<T>) -> Result<T> {
+        let value = value?;
+        let unused = self.unused_keys();
+        match self.strict && !unused.is_empty() {
+            true => Err(Error::UnusedKeys {
+                keys: unused.into_iter().map(Into::into).collect(),
+            }),
+            false => Ok(value),
+        }
+    }
+
+    /// Deserialize an instance of type `T`, reporting all missing keys and
+    /// invalid values at once.
+    ///
+    /// Invalid values are replaced with a default value of their type and
+    /// missing keys with placeholders, so that the deserialization can be
+    /// retried until it reaches the keys that are still missing.
+    ///
+    /// Values rejected by their visitor, such as an invalid `IpAddr`, abort
+    /// the deserialization. They are treated as absent when retrying, which
+    /// ends at the first of them that is required.
+    pub(crate) fn deserialize_all<T>(&mut self) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let mut missing: Vec<String> = Vec::new();
+        let mut rejected: Vec<Error> = Vec::new();
+
+        loop {
+            self.errors = Some(Errors::default());
+            let result = T::deserialize(&mut *self);
+            let mut errors = self.errors.take().unwrap_or_default().into_inner();
+
+            // Placeholders are not valid values of most types
+            errors.retain(|error| match error {
+                Error::InvalidValue { key, .. } => !missing.contains(key),
+                _ => true,
+            });
+
+            match result {
+                // The rejected value is reported already
+                Err(Error::MissingKey { key }) if self.is_rejected(&key) => {}
+                Err(error @ Error::InvalidValue { .. }) if self.reject(&error) => {
+                    rejected.push(error);
+                    continue;
+                }
+                Err(Error::MissingKey { key }) if !missing.contains(&key) => {
+                    if self.insert_placeholder(&key) {
+                        missing.push(key);
+                        continue;
+                    }
+                    errors.push(Error::MissingKey { key });
+                }
+                Err(e) => errors.push(e),
+                Ok(value) if errors.is_empty() && missing.iter().all(|key| self.is_none(key)) => {
+                    return Ok(value);
+                }
+                Ok(_) => {}
+            }
+
+            // Optional values without a value of their own are `None`
+            missing.retain(|key| !self.is_none(key));
+
+            // A missing struct is reported through the keys missing below it
+            let nested: Vec<String> = missing
+                .iter()
+                .filter(|key| {
+                    missing.iter().any(|other| {
+                        nests(other, key, &self.options.separator)
+                            .is_some_and(|skip| skip > key.len())
+                    })
+                })
+                .cloned()
+                .collect();
+            let mut all: Vec<Error> = missing
+                .into_iter()
+                .filter(|key| !nested.contains(key))
+                .map(|key| Error::MissingKey { key })
+                .collect();
+            all.extend(rejected);
+            all.extend(errors);
+
+            return Err(match all.len() {
+                1 => all.remove(0),
+                _ => Error::Aggregate { errors: all },
+            });
+        }
+    }
+
+    /// Mark the entry of an invalid value as rejected and return whether it was marked.
+    fn reject(&mut self, error: &Error) -> bool {
+        let Error::InvalidValue { key, .. } = error else {
+            return false;
+        };
+
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.name == *key && !entry.placeholder && !entry.rejected);
+        match entry {
+            Some(entry) => {
+                entry.rejected = true;
+                entry.used.set(true);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns whether the value of the missing key `name` was rejected.
+    fn is_rejected(&self, name: &str) -> bool {
+        let name = self.options.key(name);
+        self.entries
+            .iter()
+            .any(|entry| entry.rejected && self.options.key(&entry.name) == name)
+    }
+
+    /// Returns whether the placeholder of the missing key `name` was read as `None`.
+    fn is_none(&self, name: &str) -> bool {
+        let name = self.options.key(name);
+        self.entries.iter().any(|entry| {
+            entry.placeholder && entry.none.get() && self.options.key(&entry.name) == name
+        })
+    }
+
+    /// Add an empty entry for a missing key and return whether it was added.
+    fn insert_placeholder(&mut self, name: &str) -> bool {
+        let prefix = self.key_prefix();
+        let Some(key) = name.strip_prefix(prefix.as_str()) else {
+            return false;
+        };
+
+        let key = self.options.key(key);
+        if self.entries.iter().any(|entry| entry.key == key) {
+            return false;
+        }
+
+        self.entries.push(Entry {
+            name: name.into(),
+            key,
+            value: String::new(),
+            location: None,
+            used: Cell::new(false),
+            placeholder: true,
+            none: Cell::new(false),
+            rejected: false,
+        });
+        true
+    }
+
+    fn root(&self) -> Node<'_> {
+        Node {
+            entries: &self.entries,
+            errors: self.errors.as_ref(),
+            options: &self.options,
+            path: self.key_prefix(),
+            leaf: None,
+            children: (0..self.entries.len())
+                .filter(|&index| !self.entries[index].rejected)
+                .map(|index| (index, 0))
+                .collect(),
+        }
+    }
+}
 
 pub fn from_iter<T, Iter>(iter: Iter) -> Result<T>
 where
@@ -10,19 +540,14 @@ where
     from_iter_inner::<T, Iter>(None, iter)
 }
 
-pub fn from_iter_inner<T, Iter>(prefix: Option<&str>, iter: Iter) -> Result<T>
+pub fn from_iter_inner<T, Iter>(prefixed: Option<&Prefixed>, iter: Iter) -> Result<T>
 where
     T: serde::de::DeserializeOwned,
     Iter: IntoIterator<Item = (String, String)>,
 {
-    match prefix {
-        Some(pref) => envy::prefixed(pref.to_uppercase()).from_iter::<_, T>(iter),
-        None => {
-            // No prefix provided, use default behavior
-            envy::from_iter::<_, T>(iter)
-        }
-    }
-    .map_err(Error::new)
+    Deserializer::new(None, iter)
+        .prefixed(prefixed)
+        .deserialize_all()
 }
 
 /// Deserialize program-available environment variables into an instance of type `T`.
@@ -43,7 +568,7 @@ where
 /// }
 ///
 /// let value = from_env::<Test>().expect("Failed to deserialize from environment");
-/// 
+///
 /// println!("{:?}", value);
 /// ```
 pub fn from_env<T>() -> Result<T>
@@ -53,15 +578,11 @@ where
     from_env_inner(None)
 }
 
-pub fn from_env_inner<T>(prefix: Option<&str>) -> Result<T>
+pub fn from_env_inner<T>(prefixed: Option<&Prefixed>) -> Result<T>
 where
     T: serde::de::DeserializeOwned,
 {
-    match prefix {
-        Some(pref) => envy::prefixed(pref.to_uppercase()).from_env::<T>(),
-        None => envy::from_env::<T>(),
-    }
-    .map_err(Error::new)
+    from_iter_inner(prefixed, std::env::vars())
 }
 
 /// Deserialize environment variables from a string into an instance of type `T`.
@@ -73,7 +594,7 @@ where
 ///
 /// let env = "HELLO=world";
 /// let value = from_str::<Value>(env).expect("Failed to deserialize from string");
-/// 
+///
 /// println!("{:?}", value);
 /// ```
 pub fn from_str<T>(input: &str) -> Result<T>
@@ -83,18 +604,14 @@ where
     from_str_inner::<T>(None, input)
 }
 
-pub fn from_str_inner<'a, T>(prefix: Option<&'a str>, input: &'a str) -> Result<T>
+pub fn from_str_inner<T>(prefixed: Option<&Prefixed>, input: &str) -> Result<T>
 where
     T: serde::de::DeserializeOwned,
 {
-    let mut env = Vec::new();
-
-    for pair in dotenvy::from_read_iter(input.as_bytes()) {
-        let (key, value) = pair.map_err(Error::new)?;
-        env.push((key, value));
-    }
-
-    from_iter_inner::<T, _>(prefix, env)
+    let variables = parse_prefixed(prefixed, input, None)?;
+    Deserializer::from_variables(None, variables)
+        .prefixed(prefixed)
+        .deserialize_all()
 }
 
 /// Deserialize environment variables from a reader into an instance of type `T`.
@@ -118,19 +635,17 @@ where
     from_reader_inner(None, reader)
 }
 
-pub(crate) fn from_reader_inner<R, T>(prefix: Option<&str>, reader: R) -> Result<T>
+pub(crate) fn from_reader_inner<R, T>(prefixed: Option<&Prefixed>, mut reader: R) -> Result<T>
 where
     R: std::io::Read,
     T: serde::de::DeserializeOwned,
 {
-    let mut env = Vec::new();
-
-    for pair in dotenvy::from_read_iter(reader) {
-        let (key, value) = pair.map_err(Error::new)?;
-        env.push((key, value));
-    }
+    let mut input = String::new();
+    reader
+        .read_to_string(&mut input)
+        .map_err(|e| Error::io(None, e))?;
 
-    from_iter_inner::<T, _>(prefix, env)
+    from_str_inner(prefixed, &input)
 }
 
 /// Deserialize an environment variable file into an instance of type `T`.
@@ -143,7 +658,7 @@ where
 ///
 /// let path = PathBuf::from(".env");
 /// let value = from_file::<Value>(&path).expect("Failed to deserialize from file");
-/// 
+///
 /// println!("{:?}", value);
 /// ```
 pub fn from_file<T>(path: &Path) -> Result<T>
@@ -153,82 +668,2094 @@ where
     from_file_inner(None, path)
 }
 
-pub fn from_file_inner<T>(prefix: Option<&str>, path: &Path) -> Result<T>
+pub fn from_file_inner<T>(prefixed: Option<&Prefixed>, path: &Path) -> Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let input = std::fs::read_to_string(path).map_err(|e| Error::io(Some(path.into()), e))?;
+    let variables = parse_prefixed(prefixed, &input, Some(path))?;
+
+    Deserializer::from_variables(None, variables)
+        .prefixed(prefixed)
+        .deserialize_all()
+}
+
+/// Parse an envfile with the interpolation of `prefixed`, if any.
+fn parse_prefixed(
+    prefixed: Option<&Prefixed>,
+    input: &str,
+    path: Option<&Path>,
+) -> Result<Vec<Variable>> {
+    match prefixed {
+        Some(prefixed) => parse_with(input, path, &mut HashMap::new(), &prefixed.interpolation),
+        None => parse(input, path),
+    }
+}
+
+/// Deserialize environment variables from a string into an instance of type
+/// `T`, additionally returning the keys that no field of `T` consumed.
+///
+/// # Example
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_envfile::from_str_with_report;
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Config {
+///     database_url: String,
+/// }
+///
+/// let env = "DATABASE_URL=postgres://localhost\nDATABSE_USER=admin";
+/// let (config, unused) = from_str_with_report::<Config>(env).expect("Failed to deserialize");
+///
+/// assert_eq!(unused, vec!["DATABSE_USER"]);
+/// ```
+pub fn from_str_with_report<T>(input: &str) -> Result<(T, Vec<String>)>
+where
+    T: serde::de::DeserializeOwned,
+{
+    from_str_with_report_inner(None, input)
+}
+
+pub(crate) fn from_str_with_report_inner<T>(
+    prefixed: Option<&Prefixed>,
+    input: &str,
+) -> Result<(T, Vec<String>)>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let variables = parse_prefixed(prefixed, input, None)?;
+    deserialize_with_report(Deserializer::from_variables(None, variables).prefixed(prefixed))
+}
+
+/// Deserialize an environment variable file into an instance of type `T`,
+/// additionally returning the keys that no field of `T` consumed.
+pub fn from_file_with_report<T>(path: &Path) -> Result<(T, Vec<String>)>
+where
+    T: serde::de::DeserializeOwned,
+{
+    from_file_with_report_inner(None, path)
+}
+
+pub(crate) fn from_file_with_report_inner<T>(
+    prefixed: Option<&Prefixed>,
+    path: &Path,
+) -> Result<(T, Vec<String>)>
 where
     T: serde::de::DeserializeOwned,
 {
-    let mut env = Vec::new();
+    let input = std::fs::read_to_string(path).map_err(|e| Error::io(Some(path.into()), e))?;
+    let variables = parse_prefixed(prefixed, &input, Some(path))?;
+
+    deserialize_with_report(Deserializer::from_variables(None, variables).prefixed(prefixed))
+}
+
+/// Deserialize from a reader into an instance of type `T`, additionally
+/// returning the keys that no field of `T` consumed.
+pub fn from_reader_with_report<R, T>(reader: R) -> Result<(T, Vec<String>)>
+where
+    R: std::io::Read,
+    T: serde::de::DeserializeOwned,
+{
+    from_reader_with_report_inner(None, reader)
+}
+
+pub(crate) fn from_reader_with_report_inner<R, T>(
+    prefixed: Option<&Prefixed>,
+    mut reader: R,
+) -> Result<(T, Vec<String>)>
+where
+    R: std::io::Read,
+    T: serde::de::DeserializeOwned,
+{
+    let mut input = String::new();
+    reader
+        .read_to_string(&mut input)
+        .map_err(|e| Error::io(None, e))?;
+
+    from_str_with_report_inner(prefixed, &input)
+}
+
+/// Deserialize program-available environment variables into an instance of
+/// type `T`, additionally returning the variables that no field of `T` consumed.
+pub fn from_env_with_report<T>() -> Result<(T, Vec<String>)>
+where
+    T: serde::de::DeserializeOwned,
+{
+    from_env_with_report_inner(None)
+}
+
+pub(crate) fn from_env_with_report_inner<T>(prefixed: Option<&Prefixed>) -> Result<(T, Vec<String>)>
+where
+    T: serde::de::DeserializeOwned,
+{
+    deserialize_with_report(Deserializer::new(None, std::env::vars()).prefixed(prefixed))
+}
+
+pub(crate) fn deserialize_with_report<T>(mut deserializer: Deserializer) -> Result<(T, Vec<String>)>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let value = deserializer.deserialize_all::<T>()?;
+    let unused = deserializer
+        .unused_keys()
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    Ok((value, unused))
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.finish(self.root().deserialize_any(visitor))
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.finish(self.root().deserialize_option(visitor))
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.finish(self.root().deserialize_newtype_struct(name, visitor))
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.finish(self.root().deserialize_map(visitor))
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.finish(self.root().deserialize_struct(name, fields, visitor))
+    }
 
-    for pair in dotenvy::from_filename_iter(path).map_err(Error::new)? {
-        let (key, value) = pair.map_err(Error::new)?;
-        env.push((key, value));
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.finish(self.root().deserialize_enum(name, variants, visitor))
     }
 
-    from_iter_inner::<T, _>(prefix, env)
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct identifier
+        ignored_any
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use std::{
-        env::{set_var, vars},
-        fs::write,
-        io::{Cursor, Seek, SeekFrom},
-    };
+/// Returns whether `key` equals `name` or continues with a nested segment of it.
+fn nests(key: &str, name: &str, separator: &str) -> Option<usize> {
+    if key == name {
+        Some(name.len())
+    } else if key.starts_with(name) && key[name.len()..].starts_with(separator) {
+        Some(name.len() + separator.len())
+    } else {
+        None
+    }
+}
 
-    use tempfile::NamedTempFile;
+/// Returns whether `segment` is the index of a sequence element.
+fn is_index(segment: &str) -> bool {
+    !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit())
+}
 
-    use super::{from_env, from_file, from_reader, from_str};
-    use crate::Value;
+/// A position in the tree of underscore-joined keys.
+///
+/// `leaf` is the variable whose key ends exactly at this node, `children` are
+/// the variables nested below it along with the offset at which the remainder
+/// of their key starts.
+struct Node<'a> {
+    entries: &'a [Entry],
+    errors: Option<&'a Errors>,
+    options: &'a Options,
+    /// Key of this node as used in error messages, ending with a separator.
+    path: String,
+    leaf: Option<&'a Entry>,
+    children: Vec<(usize, usize)>,
+}
 
-    #[test]
-    fn from_env_test() {
-        unsafe {
-            set_var("SERDE_ENVFILE", "HELLO WORLD");
+impl<'a> Node<'a> {
+    /// Create an empty node nested below this node.
+    fn child(&self, name: &str) -> Node<'a> {
+        Node {
+            entries: self.entries,
+            errors: self.errors,
+            options: self.options,
+            path: format!(
+                "{}{}{}",
+                self.path,
+                self.options.key_case.apply(name),
+                self.options.separator
+            ),
+            leaf: None,
+            children: Vec::new(),
         }
+    }
 
-        let env: Value = from_env().unwrap();
-
-        assert_eq!(env.len(), vars().collect::<Vec<(String, String)>>().len());
+    /// Returns the key of this node as used in error messages.
+    fn key(&self) -> String {
+        self.path
+            .strip_suffix(self.options.separator.as_str())
+            .unwrap_or(&self.path)
+            .into()
+    }
 
-        for (key, value) in vars() {
-            assert_eq!(&value, env.get(&key.to_lowercase()).unwrap());
+    /// Mark the leaf of this node as used without reading its value.
+    fn consume(&self) {
+        if let Some(entry) = self.leaf {
+            entry.used.set(true);
         }
     }
 
-    #[test]
-    fn from_str_test() {
-        let input = "HELLO=world";
-        let env: Value = from_str(input).unwrap();
+    fn remainder(&self, (index, offset): (usize, usize)) -> &'a str {
+        &self.entries[index].key[offset..]
+    }
 
-        assert_eq!(env.len(), 1);
-        assert_eq!("world", env.get("hello").unwrap());
+    fn text(&self) -> Result<Text<'a>> {
+        self.consume();
+        match self.leaf {
+            Some(entry) => Ok(Text {
+                name: &entry.name,
+                value: Cow::Borrowed(&entry.value),
+                location: entry.location.as_ref(),
+                errors: self.errors,
+                options: self.options,
+            }),
+            // Nested keys are unrelated variables sharing the start of the key
+            None => Err(Error::MissingKey { key: self.key() }),
+        }
     }
 
-    #[test]
-    fn deserialize_from_reader() {
-        //* Given
-        let input = "HELLO=world";
+    /// Distributes the children of this node among the given struct fields.
+    ///
+    /// A key matching a field name exactly becomes the leaf of that field,
+    /// otherwise the key belongs to the longest field name it is nested under.
+    fn fields(self, fields: &'static [&'static str]) -> Vec<(&'static str, Node<'a>)> {
+        let names: Vec<String> = fields
+            .iter()
+            .map(|field| self.options.matched(field))
+            .collect();
+        let mut nodes: Vec<Node<'a>> = fields.iter().map(|field| self.child(field)).collect();
 
-        let reader = Cursor::new(input);
+        for &child in &self.children {
+            let key = self.remainder(child);
 
-        //* When
-        let env: Value = from_reader(reader).expect("Failed to deserialize from reader");
+            if let Some(position) = names.iter().position(|name| key == name) {
+                nodes[position].leaf = Some(&self.entries[child.0]);
+                continue;
+            }
 
-        //* Then
-        assert_eq!(env.len(), 1);
-        assert_eq!("world", env.get("hello").unwrap());
+            let nested = names
+                .iter()
+                .enumerate()
+                .filter_map(|(position, name)| {
+                    nests(key, name, &self.options.separator).map(|skip| (position, skip))
+                })
+                .max_by_key(|&(_, skip)| skip);
+
+            if let Some((position, skip)) = nested {
+                nodes[position].children.push((child.0, child.1 + skip));
+            }
+        }
+
+        fields
+            .iter()
+            .copied()
+            .zip(nodes)
+            .filter(|(_, node)| node.leaf.is_some() || !node.children.is_empty())
+            .collect()
     }
 
-    #[test]
-    fn from_file_test() {
-        let input = "HELLO=world";
-        let mut file = NamedTempFile::new().unwrap();
-        write(file.path(), input).unwrap();
-        file.seek(SeekFrom::Start(0)).unwrap();
+    /// Groups the children of this node into the elements of an indexed
+    /// sequence, ordered by their index. Missing indices are empty elements,
+    /// read as `None` or reported as missing.
+    ///
+    /// Children whose key does not start with an index are left unused.
+    fn elements(self) -> impl Iterator<Item = Node<'a>> {
+        let mut elements: BTreeMap<usize, Node<'a>> = BTreeMap::new();
 
-        let env: Value = from_file(file.path()).expect("Failed to deserialize from file");
+        for &child in &self.children {
+            let key = self.remainder(child);
+            let index = key
+                .split(self.options.separator.as_str())
+                .next()
+                .unwrap_or(key);
+            let Some(position) = index.parse::<usize>().ok().filter(|_| is_index(index)) else {
+                continue;
+            };
 
-        assert_eq!(env.len(), 1);
-        assert_eq!("world", env.get("hello").unwrap());
+            let node = elements
+                .entry(position)
+                .or_insert_with(|| self.child(index));
+            if index.len() == key.len() {
+                node.leaf = Some(&self.entries[child.0]);
+            } else {
+                node.children.push((
+                    child.0,
+                    child.1 + index.len() + self.options.separator.len(),
+                ));
+            }
+        }
+
+        // Missing indices are empty elements rather than shifting the later ones
+        let len = elements.keys().next_back().map_or(0, |&last| last + 1);
+        (0..len).map(move |position| {
+            elements
+                .remove(&position)
+                .unwrap_or_else(|| self.child(&position.to_string()))
+        })
+    }
+}
+
+macro_rules! forward_to_text {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: de::Visitor<'de>,
+            {
+                self.text()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Node<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if self.leaf.is_some() && self.children.is_empty() {
+            self.text()?.deserialize_any(visitor)
+        } else if !self.options.infer {
+            // Serde buffers internally tagged and untagged enums this way before
+            // the types of their fields are known, which would leave every value a string
+            let options = Options {
+                infer: true,
+                ..self.options.clone()
+            };
+            Node {
+                entries: self.entries,
+                errors: self.errors,
+                options: &options,
+                path: self.path,
+                leaf: self.leaf,
+                children: self.children,
+            }
+            .deserialize_map(visitor)
+        } else {
+            self.deserialize_map(visitor)
+        }
+    }
+
+    forward_to_text! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char deserialize_str
+        deserialize_string deserialize_bytes deserialize_byte_buf deserialize_identifier
+    }
+
+    /// Sequences are either a comma-separated value or indexed keys nested
+    /// below this node, such as `UPSTREAMS_0_HOST`.
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if self.children.is_empty() {
+            return self.text()?.deserialize_seq(visitor);
+        }
+
+        SeqDeserializer::new(self.elements()).deserialize_seq(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.leaf {
+            // Read as `None` after a scalar was requested from nested keys alone
+            Some(entry) if entry.placeholder => {
+                entry.none.set(true);
+                visitor.visit_none()
+            }
+            Some(entry) if !self.options.none_style.is_none(&entry.value) => {
+                visitor.visit_some(self)
+            }
+            None if !self.children.is_empty() => visitor.visit_some(self),
+            _ => {
+                self.consume();
+                visitor.visit_none()
+            }
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.consume();
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.consume();
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_map(MapAccess {
+            entries: self.entries,
+            errors: self.errors,
+            options: self.options,
+            path: self.path,
+            remaining: self.children,
+            value: None,
+        })
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let path = self.path.clone();
+        let options = self.options;
+        let exhausted = Cell::new(false);
+
+        visitor
+            .visit_map(StructAccess {
+                fields: self.fields(fields).into_iter(),
+                value: None,
+                exhausted: &exhausted,
+            })
+            .map_err(|e| match e {
+                // Fields missing from this struct are reported once all present fields were visited
+                Error::MissingKey { key } if exhausted.get() => {
+                    // Recover the field name to apply the key case to it
+                    let field = fields.iter().find(|field| field.to_uppercase() == key);
+                    let key = field.map_or(key, |field| options.key_case.apply(field));
+                    Error::MissingKey {
+                        key: format!("{path}{key}"),
+                    }
+                }
+                e => e,
+            })
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if self.children.is_empty() {
+            return self.text()?.deserialize_enum(_name, variants, visitor);
+        }
+
+        let key = self
+            .children
+            .first()
+            .map(|&(index, _)| &self.entries[index].name);
+        let key = key.cloned().unwrap_or_else(|| self.key());
+        match self.fields(variants).into_iter().next() {
+            Some((variant, node)) => visitor.visit_enum(EnumAccess { variant, node }),
+            None => Err(Error::InvalidKey { key }),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+}
+
+impl<'de, 'a> de::IntoDeserializer<'de, Error> for Node<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+/// Yields the fields of a struct that are present in the input.
+struct StructAccess<'a, 'b> {
+    fields: std::vec::IntoIter<(&'static str, Node<'a>)>,
+    value: Option<Node<'a>>,
+    /// Set once all fields have been yielded.
+    exhausted: &'b Cell<bool>,
+}
+
+impl<'de> de::MapAccess<'de> for StructAccess<'_, '_> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match self.fields.next() {
+            Some((field, node)) => {
+                self.value = Some(node);
+                seed.deserialize(StrDeserializer::<Error>::new(field))
+                    .map(Some)
+            }
+            None => {
+                self.exhausted.set(true);
+                Ok(None)
+            }
+        }
+    }
+
+    fn next_value_seed<S>(&mut self, seed: S) -> Result<S::Value>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        let node = self.value.take().expect("value requested before key");
+        seed.deserialize(node)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.fields.len())
+    }
+}
+
+/// Yields the entries of a map.
+///
+/// When the key and value are requested together, which is how map types
+/// deserialize, the value is deserialized first so that its shape decides how
+/// many key segments belong to the map key. Otherwise, every remaining key is
+/// yielded as is.
+struct MapAccess<'a> {
+    entries: &'a [Entry],
+    errors: Option<&'a Errors>,
+    options: &'a Options,
+    path: String,
+    remaining: Vec<(usize, usize)>,
+    value: Option<Node<'a>>,
+}
+
+impl<'a> MapAccess<'a> {
+    fn take(&mut self) -> Option<(&'a str, Node<'a>)> {
+        if self.remaining.is_empty() {
+            return None;
+        }
+
+        let (index, offset) = self.remaining.remove(0);
+        let entry = &self.entries[index];
+        let key = &entry.key[offset..];
+        Some((
+            key,
+            Node {
+                entries: self.entries,
+                errors: self.errors,
+                options: self.options,
+                path: format!(
+                    "{}{}{}",
+                    self.path,
+                    self.options.key_case.apply(key),
+                    self.options.separator
+                ),
+                leaf: Some(entry),
+                children: Vec::new(),
+            },
+        ))
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match self.take() {
+            Some((key, node)) => {
+                self.value = Some(node);
+                seed.deserialize(MapKey {
+                    key,
+                    path: &self.path,
+                    options: self.options,
+                })
+                .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<S>(&mut self, seed: S) -> Result<S::Value>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        let node = self.value.take().expect("value requested before key");
+        seed.deserialize(node)
+    }
+
+    fn next_entry_seed<K, S>(&mut self, kseed: K, vseed: S) -> Result<Option<(K::Value, S::Value)>>
+    where
+        K: de::DeserializeSeed<'de>,
+        S: de::DeserializeSeed<'de>,
+    {
+        if self.remaining.is_empty() {
+            return Ok(None);
+        }
+
+        let mut key = None;
+        let value = vseed.deserialize(MapValue {
+            access: self,
+            key: &mut key,
+        })?;
+        let key = key.expect("map value did not claim a key");
+        let key = kseed.deserialize(MapKey {
+            key: &key,
+            path: &self.path,
+            options: self.options,
+        })?;
+        Ok(Some((key, value)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining.len())
+    }
+}
+
+/// Deserializer for a map value whose key has not been decided yet.
+///
+/// Scalars claim the next remaining key as a whole. Structs, maps and enums
+/// split it into the map key and the nested segments they expect, and claim
+/// every remaining key below the same map key.
+struct MapValue<'a, 'b> {
+    access: &'b mut MapAccess<'a>,
+    key: &'b mut Option<String>,
+}
+
+impl<'a> MapValue<'a, '_> {
+    fn scalar(self) -> Result<Node<'a>> {
+        let (key, node) = self
+            .access
+            .take()
+            .expect("map value without remaining keys");
+        *self.key = Some(key.into());
+        Ok(node)
+    }
+
+    /// Claims the group of keys below the first candidate map key accepted by
+    /// `nested`, which is called with the rest of the key.
+    fn group(self, nested: impl Fn(&str, &Options) -> bool) -> Result<Node<'a>> {
+        let access = self.access;
+        let separator = access.options.separator.as_str();
+        let (index, offset) = access.remaining[0];
+        let key = &access.entries[index].key[offset..];
+
+        let split = key
+            .match_indices(separator)
+            .map(|(position, _)| position)
+            .find(|&position| nested(&key[position + separator.len()..], access.options))
+            .unwrap_or(key.len());
+        let name = &key[..split];
+
+        let mut node = Node {
+            entries: access.entries,
+            errors: access.errors,
+            options: access.options,
+            path: format!(
+                "{}{}{}",
+                access.path,
+                access.options.key_case.apply(name),
+                separator
+            ),
+            leaf: None,
+            children: Vec::new(),
+        };
+        access.remaining.retain(|&(index, offset)| {
+            let entry = &access.entries[index];
+            match nests(&entry.key[offset..], name, separator) {
+                Some(skip) if skip == name.len() => {
+                    node.leaf = Some(entry);
+                    false
+                }
+                Some(skip) => {
+                    node.children.push((index, offset + skip));
+                    false
+                }
+                None => true,
+            }
+        });
+
+        *self.key = Some(name.into());
+        Ok(node)
+    }
+
+    /// Claims the keys of an indexed sequence, or the next key as a whole.
+    fn sequence(self) -> Result<Node<'a>> {
+        self.group(|rest, options| {
+            rest.split_once(options.separator.as_str())
+                .is_some_and(|(index, _)| is_index(index))
+        })
+    }
+}
+
+macro_rules! forward_to_scalar {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: de::Visitor<'de>,
+            {
+                self.scalar()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for MapValue<'_, '_> {
+    type Error = Error;
+
+    forward_to_scalar! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32
+        deserialize_i64 deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32
+        deserialize_u64 deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char
+        deserialize_str deserialize_string deserialize_bytes deserialize_byte_buf
+        deserialize_unit deserialize_identifier deserialize_ignored_any
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.sequence()?.deserialize_seq(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let access = &self.access;
+        let (index, _) = access.remaining[0];
+        if access
+            .options
+            .none_style
+            .is_none(&access.entries[index].value)
+        {
+            return self.scalar()?.deserialize_option(visitor);
+        }
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.scalar()?.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.sequence()?.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.sequence()?
+            .deserialize_tuple_struct(name, len, visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.group(|_, _| true)?.deserialize_map(visitor)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.group(|rest, options| {
+            fields
+                .iter()
+                .any(|field| nests(rest, &options.matched(field), &options.separator).is_some())
+        })?
+        .deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.group(|rest, options| {
+            variants
+                .iter()
+                .any(|variant| nests(rest, &options.matched(variant), &options.separator).is_some())
+        })?
+        .deserialize_enum(name, variants, visitor)
+    }
+}
+
+/// Deserializer for a map key, which is parsed into integers, booleans and
+/// unit variants if requested.
+struct MapKey<'a> {
+    key: &'a str,
+    /// Key of the map, used in error messages.
+    path: &'a str,
+    options: &'a Options,
+}
+
+impl MapKey<'_> {
+    fn invalid(&self) -> Error {
+        Error::InvalidKey {
+            key: format!("{}{}", self.path, self.options.key_case.apply(self.key)),
+        }
+    }
+}
+
+macro_rules! forward_parsed_key {
+    ($($ty:ident => $method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: de::Visitor<'de>,
+            {
+                match self.key.parse::<$ty>() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(self.invalid()),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for MapKey<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_str(self.key)
+    }
+
+    forward_parsed_key! {
+        i8 => deserialize_i8 => visit_i8,
+        i16 => deserialize_i16 => visit_i16,
+        i32 => deserialize_i32 => visit_i32,
+        i64 => deserialize_i64 => visit_i64,
+        i128 => deserialize_i128 => visit_i128,
+        u8 => deserialize_u8 => visit_u8,
+        u16 => deserialize_u16 => visit_u16,
+        u32 => deserialize_u32 => visit_u32,
+        u64 => deserialize_u64 => visit_u64,
+        u128 => deserialize_u128 => visit_u128,
+        char => deserialize_char => visit_char,
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        // Keys may have been transformed to another case
+        if self.key.eq_ignore_ascii_case("true") {
+            visitor.visit_bool(true)
+        } else if self.key.eq_ignore_ascii_case("false") {
+            visitor.visit_bool(false)
+        } else {
+            Err(self.invalid())
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let variant = variants
+            .iter()
+            .find(|variant| self.options.matched(variant) == self.key)
+            .copied()
+            .unwrap_or(self.key);
+        visitor.visit_enum(StrDeserializer::<Error>::new(variant))
+    }
+
+    serde::forward_to_deserialize_any! {
+        f32 f64 str string bytes byte_buf option unit unit_struct seq tuple tuple_struct
+        map struct identifier ignored_any
+    }
+}
+
+/// Selects the variant of an enum from the nested keys below it.
+struct EnumAccess<'a> {
+    variant: &'static str,
+    node: Node<'a>,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for EnumAccess<'a> {
+    type Error = Error;
+    type Variant = Node<'a>;
+
+    fn variant_seed<S>(self, seed: S) -> Result<(S::Value, Self::Variant)>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(StrDeserializer::<Error>::new(self.variant))?;
+        Ok((variant, self.node))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Node<'_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        self.consume();
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S>(self, seed: S) -> Result<S::Value>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        de::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}
+
+/// Deserializer for the textual value of a single variable.
+struct Text<'a> {
+    name: &'a str,
+    value: Cow<'a, str>,
+    location: Option<&'a Location>,
+    /// Collects invalid values instead of failing, if set.
+    errors: Option<&'a Errors>,
+    options: &'a Options,
+}
+
+impl Text<'_> {
+    fn invalid<E>(&self, expected: &'static str, source: E) -> Error
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        Error::InvalidValue {
+            key: self.name.into(),
+            value: self.value.to_string(),
+            expected,
+            location: self.location.cloned(),
+            source: Some(Box::new(source)),
+        }
+    }
+
+    /// Attach the key, value and location of this variable to an error raised
+    /// by a visitor, such as the parse error of an `IpAddr`.
+    fn locate(&self, error: Error, expected: &'static str) -> Error {
+        match error {
+            Error::Message(message) => Error::InvalidValue {
+                key: self.name.into(),
+                value: self.value.to_string(),
+                expected,
+                location: self.location.cloned(),
+                source: Some(message.into()),
+            },
+            error => error,
+        }
+    }
+}
+
+/// Expectation of errors raised by visitors, which describe it themselves.
+const VALID_VALUE: &str = "a valid value";
+
+macro_rules! forward_parsed {
+    ($($ty:ident => $method:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: de::Visitor<'de>,
+            {
+                match self.value.parse::<$ty>() {
+                    Ok(value) => value
+                        .into_deserializer()
+                        .$method(visitor)
+                        .map_err(|e| self.locate(e, stringify!($ty))),
+                    Err(e) => {
+                        let error = self.invalid(stringify!($ty), e);
+                        match self.errors {
+                            Some(errors) => {
+                                errors.borrow_mut().push(error);
+                                <$ty>::default().into_deserializer().$method(visitor)
+                            }
+                            None => Err(error),
+                        }
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Text<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if self.options.infer {
+            let numeric = |c: char| c.is_ascii_digit() || "+-.eE".contains(c);
+            let result = if let Ok(value) = self.value.parse::<bool>() {
+                visitor.visit_bool(value)
+            } else if let Ok(value) = self.value.parse::<u64>() {
+                visitor.visit_u64(value)
+            } else if let Ok(value) = self.value.parse::<i64>() {
+                visitor.visit_i64(value)
+            } else if self.value.chars().all(numeric)
+                && let Ok(value) = self.value.parse::<f64>()
+            {
+                visitor.visit_f64(value)
+            } else {
+                return self.deserialize_str(visitor);
+            };
+            return result.map_err(|e| self.locate(e, VALID_VALUE));
+        }
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor
+            .visit_str(&self.value)
+            .map_err(|e| self.locate(e, VALID_VALUE))
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    forward_parsed! {
+        bool => deserialize_bool,
+        i8 => deserialize_i8,
+        i16 => deserialize_i16,
+        i32 => deserialize_i32,
+        i64 => deserialize_i64,
+        i128 => deserialize_i128,
+        u8 => deserialize_u8,
+        u16 => deserialize_u16,
+        u32 => deserialize_u32,
+        u64 => deserialize_u64,
+        u128 => deserialize_u128,
+        f32 => deserialize_f32,
+        f64 => deserialize_f64,
+        char => deserialize_char,
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let encoding = self.options.byte_encoding;
+        match encoding.decode(&self.value) {
+            Ok(bytes) => visitor
+                .visit_bytes(&bytes)
+                .map_err(|e| self.locate(e, encoding.name())),
+            Err(e) => {
+                let error = self.invalid(encoding.name(), e);
+                match self.errors {
+                    Some(errors) => {
+                        errors.borrow_mut().push(error);
+                        visitor.visit_bytes(&[])
+                    }
+                    None => Err(error),
+                }
+            }
+        }
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if self.options.none_style.is_none(&self.value) {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor
+            .visit_unit()
+            .map_err(|e| self.locate(e, VALID_VALUE))
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let Text {
+            name,
+            location,
+            errors,
+            options,
+            ..
+        } = self;
+        let elements = split(&self.value, options.delimiter);
+        SeqDeserializer::new(elements.into_iter().map(|value| Text {
+            name,
+            value,
+            location,
+            errors,
+            options,
+        }))
+        .deserialize_seq(visitor)
+        .map_err(|e| self.locate(e, "a sequence"))
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let variant = match (self.errors, variants.first()) {
+            (Some(errors), Some(first)) if !variants.contains(&&*self.value) => {
+                errors.borrow_mut().push(Error::InvalidValue {
+                    key: self.name.into(),
+                    value: self.value.to_string(),
+                    expected: name,
+                    location: self.location.cloned(),
+                    source: None,
+                });
+                first
+            }
+            _ => &*self.value,
+        };
+        visitor
+            .visit_enum(StrDeserializer::<Error>::new(variant))
+            .map_err(|e| self.locate(e, name))
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        map struct
+    }
+}
+
+impl<'de> de::IntoDeserializer<'de, Error> for Text<'_> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        env::{set_var, vars},
+        error::Error as _,
+        fs::write,
+        io::{Cursor, Seek, SeekFrom},
+    };
+
+    use tempfile::NamedTempFile;
+
+    use serde::{Deserialize as _, Serialize as _};
+
+    use super::{
+        Deserializer, from_env, from_env_with_report, from_file, from_reader,
+        from_reader_with_report, from_str, from_str_with_report,
+    };
+    use crate::{Error, Interpolation, KeyCase, NoneStyle, Serializer, Value};
+
+    #[test]
+    fn from_env_test() {
+        unsafe {
+            set_var("SERDE_ENVFILE", "HELLO WORLD");
+        }
+
+        let env: Value = from_env().unwrap();
+
+        assert_eq!(env.len(), vars().collect::<Vec<(String, String)>>().len());
+
+        for (key, value) in vars() {
+            assert_eq!(&value, env.get(&key.to_lowercase()).unwrap());
+        }
+    }
+
+    #[test]
+    fn from_str_test() {
+        let input = "HELLO=world";
+        let env: Value = from_str(input).unwrap();
+
+        assert_eq!(env.len(), 1);
+        assert_eq!("world", env.get("hello").unwrap());
+    }
+
+    #[test]
+    fn deserialize_from_reader() {
+        //* Given
+        let input = "HELLO=world";
+
+        let reader = Cursor::new(input);
+
+        //* When
+        let env: Value = from_reader(reader).expect("Failed to deserialize from reader");
+
+        //* Then
+        assert_eq!(env.len(), 1);
+        assert_eq!("world", env.get("hello").unwrap());
+    }
+
+    #[test]
+    fn from_file_test() {
+        let input = "HELLO=world";
+        let mut file = NamedTempFile::new().unwrap();
+        write(file.path(), input).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+
+        let env: Value = from_file(file.path()).expect("Failed to deserialize from file");
+
+        assert_eq!(env.len(), 1);
+        assert_eq!("world", env.get("hello").unwrap());
+    }
+
+    #[test]
+    fn deserialize_nested_struct() {
+        //* Given
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Database {
+            host: String,
+            port: u16,
+        }
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Config {
+            database_url: String,
+            database: Database,
+            replica: Option<Database>,
+        }
+
+        let input =
+            "DATABASE_URL=postgres://localhost\nDATABASE_HOST=localhost\nDATABASE_PORT=5432";
+
+        //* When
+        let config: Config = from_str(input).expect("Failed to deserialize nested struct");
+
+        //* Then
+        let expected = Config {
+            database_url: "postgres://localhost".into(),
+            database: Database {
+                host: "localhost".into(),
+                port: 5432,
+            },
+            replica: None,
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn deserialize_map_of_structs() {
+        //* Given
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Upstream {
+            host: String,
+            port: u16,
+        }
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Config {
+            upstreams: HashMap<String, Upstream>,
+            labels: HashMap<String, String>,
+        }
+
+        let input = "UPSTREAMS_PRIMARY_ONE_HOST=a\nUPSTREAMS_PRIMARY_ONE_PORT=1\nUPSTREAMS_BACKUP_HOST=b\nUPSTREAMS_BACKUP_PORT=2\nLABELS_TEAM_NAME=core";
+
+        //* When
+        let config: Config = from_str(input).expect("Failed to deserialize map of structs");
+
+        //* Then
+        assert_eq!(config.upstreams.len(), 2);
+        assert_eq!(
+            config.upstreams.get("primary_one"),
+            Some(&Upstream {
+                host: "a".into(),
+                port: 1
+            })
+        );
+        assert_eq!(config.upstreams.get("backup").unwrap().port, 2);
+        assert_eq!(config.labels.get("team_name").unwrap(), "core");
+    }
+
+    #[test]
+    fn deserialize_enum_variants() {
+        //* Given
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        enum Mode {
+            Off,
+            Limit(u8),
+        }
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Config {
+            a: Mode,
+            b: Mode,
+        }
+
+        let input = "A=Off\nB_LIMIT=3";
+
+        //* When
+        let config: Config = from_str(input).expect("Failed to deserialize enum variants");
+
+        //* Then
+        assert_eq!(
+            config,
+            Config {
+                a: Mode::Off,
+                b: Mode::Limit(3)
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_tagged_enums() {
+        //* Given
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        #[serde(tag = "kind", rename_all = "lowercase")]
+        enum Storage {
+            S3 { bucket: String, port: u16 },
+            Local { path: String },
+        }
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        #[serde(tag = "kind", content = "options", rename_all = "lowercase")]
+        enum Cache {
+            Redis { url: String, ttl: u32 },
+            Memory,
+        }
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Config {
+            storage: Storage,
+            backup: Storage,
+            cache: Cache,
+        }
+
+        let input = "STORAGE_KIND=s3\nSTORAGE_BUCKET=assets\nSTORAGE_PORT=9000\n\
+            BACKUP_KIND=local\nBACKUP_PATH=/tmp\n\
+            CACHE_KIND=redis\nCACHE_OPTIONS_URL=redis://cache\nCACHE_OPTIONS_TTL=60";
+
+        //* When
+        let config: Config = from_str(input).expect("Failed to deserialize tagged enums");
+        let memory: Cache = from_str("KIND=memory").expect("Failed to deserialize unit variant");
+
+        //* Then
+        assert_eq!(
+            config,
+            Config {
+                storage: Storage::S3 {
+                    bucket: "assets".into(),
+                    port: 9000
+                },
+                backup: Storage::Local {
+                    path: "/tmp".into()
+                },
+                cache: Cache::Redis {
+                    url: "redis://cache".into(),
+                    ttl: 60
+                },
+            }
+        );
+        assert_eq!(memory, Cache::Memory);
+
+        // Values looking like numbers are read as such before the field types are known
+        let numeric = from_str::<Storage>("KIND=local\nPATH=123");
+        assert!(numeric.is_err());
+    }
+
+    #[test]
+    fn invalid_value_error() {
+        //* Given
+        #[derive(Debug, serde::Deserialize)]
+        struct Config {
+            #[allow(dead_code)]
+            port: u16,
+        }
+
+        let input = "# ports\nPORT=eighty";
+        let mut file = NamedTempFile::new().unwrap();
+        write(file.path(), input).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+
+        //* When
+        let error = from_file::<Config>(file.path()).expect_err("Expected an invalid value");
+
+        //* Then
+        assert!(error.source().is_some());
+        match error {
+            Error::InvalidValue {
+                key,
+                value,
+                expected,
+                location: Some(location),
+                ..
+            } => {
+                assert_eq!(key, "PORT");
+                assert_eq!(value, "eighty");
+                assert_eq!(expected, "u16");
+                assert_eq!(location.path.as_deref(), Some(file.path()));
+                assert_eq!((location.line, location.column), (2, 6));
+            }
+            e => panic!("Unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn visitor_errors_are_located() {
+        //* Given
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Upstream {
+            host: String,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Config {
+            ip: std::net::IpAddr,
+        }
+
+        //* When
+        let ip = from_str::<Config>("# address\nIP=nope");
+        let nested = from_str::<HashMap<String, HashMap<String, Upstream>>>("UPS_MY_HOST_HOST=a");
+        let mut deserializer = Deserializer::from_str("IP=::x").expect("Failed to parse");
+        let direct = Config::deserialize(&mut deserializer);
+
+        //* Then
+        match ip {
+            Err(Error::InvalidValue {
+                key,
+                value,
+                location: Some(location),
+                source: Some(source),
+                ..
+            }) => {
+                assert_eq!(key, "IP");
+                assert_eq!(value, "nope");
+                assert_eq!((location.line, location.column), (2, 4));
+                assert_eq!(source.to_string(), "invalid IP address syntax");
+            }
+            r => panic!("Unexpected result: {r:?}"),
+        }
+        assert!(matches!(direct, Err(Error::InvalidValue { key, .. }) if key == "IP"));
+        assert!(matches!(nested, Err(Error::MissingKey { key }) if key == "UPS_MY_HOST"));
+    }
+
+    #[test]
+    fn nested_keys_are_not_values() {
+        //* Given
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Optional {
+            user: Option<String>,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Required {
+            user: String,
+        }
+
+        let env = "USER_TYPE=x";
+
+        //* When
+        let optional = from_str::<Optional>(env).expect("Failed to deserialize");
+        let (_, unused) = from_str_with_report::<Optional>(env).expect("Failed to deserialize");
+        let required = from_str::<Required>(env);
+
+        //* Then
+        assert_eq!(optional, Optional { user: None });
+        assert_eq!(unused, vec!["USER_TYPE"]);
+        assert!(matches!(required, Err(Error::MissingKey { key }) if key == "USER"));
+    }
+
+    #[test]
+    fn missing_key_error() {
+        //* Given
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Database {
+            host: String,
+            port: u16,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Config {
+            database: Database,
+        }
+
+        //* When
+        let error =
+            from_str::<Config>("DATABASE_HOST=localhost").expect_err("Expected a missing key");
+
+        //* Then
+        assert!(matches!(error, Error::MissingKey { key } if key == "DATABASE_PORT"));
+    }
+
+    #[test]
+    fn io_error_keeps_source() {
+        //* Given
+        let path = std::env::temp_dir().join("serde-envfile-missing.env");
+
+        //* When
+        let error = from_file::<Value>(&path).expect_err("Expected an I/O error");
+
+        //* Then
+        match &error {
+            Error::Io {
+                path: Some(p),
+                source,
+            } => {
+                assert_eq!(p, &path);
+                assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+            }
+            e => panic!("Unexpected error: {e}"),
+        }
+        assert!(error.source().is_some());
+    }
+
+    #[test]
+    fn deserializer_with_path_to_error() {
+        //* Given
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Database {
+            port: u16,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Config {
+            database: Database,
+        }
+
+        let sources = [
+            Deserializer::from_str("APP_DATABASE_PORT=eighty").unwrap(),
+            Deserializer::from_reader(Cursor::new("APP_DATABASE_PORT=eighty")).unwrap(),
+            Deserializer::from_pairs([("APP_DATABASE_PORT", "eighty")]),
+        ];
+
+        for deserializer in sources {
+            //* When
+            let mut deserializer = deserializer.prefix("APP_");
+            let error = serde_path_to_error::deserialize::<_, Config>(&mut deserializer)
+                .expect_err("Expected an invalid value");
+
+            //* Then
+            assert_eq!(error.path().to_string(), "database.port");
+            assert!(
+                matches!(error.inner(), Error::InvalidValue { key, .. } if key == "APP_DATABASE_PORT")
+            );
+        }
+    }
+
+    #[test]
+    fn deserializer_from_file_and_env() {
+        //* Given
+        let file = NamedTempFile::new().unwrap();
+        write(file.path(), "HELLO=world").unwrap();
+
+        //* When
+        let value = Value::deserialize(&mut Deserializer::from_file(file.path()).unwrap()).unwrap();
+        let env = Value::deserialize(&mut Deserializer::from_env()).unwrap();
+
+        //* Then
+        assert_eq!(value.get("hello").unwrap(), "world");
+        assert_eq!(env.get("path"), std::env::var("PATH").ok().as_ref());
+    }
+
+    #[test]
+    fn report_unused_keys() {
+        //* Given
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Database {
+            url: String,
+            #[serde(default)]
+            pool: Option<u8>,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Config {
+            database: Database,
+            #[serde(skip)]
+            skipped: String,
+            flag: (),
+        }
+
+        let env = "DATABASE_URL=postgres://localhost\nDATABASE_POOOL=5\nDATABSE_URL=typo\nSKIPPED=1\nFLAG=";
+
+        //* When
+        let (_, unused) = from_str_with_report::<Config>(env).expect("Failed to deserialize");
+        let strict = Config::deserialize(&mut Deserializer::from_str(env).unwrap().strict(true));
+
+        //* Then
+        assert_eq!(unused, vec!["DATABASE_POOOL", "DATABSE_URL", "SKIPPED"]);
+        match strict {
+            Err(Error::UnusedKeys { keys }) => assert_eq!(keys, unused),
+            r => panic!("Unexpected result: {r:?}"),
+        }
+        assert!(from_str_with_report::<Value>(env).unwrap().1.is_empty());
+
+        let (_, unused) =
+            from_reader_with_report::<_, Config>(Cursor::new(env)).expect("Failed to deserialize");
+        assert_eq!(unused, vec!["DATABASE_POOOL", "DATABSE_URL", "SKIPPED"]);
+        let (_, unused) = from_env_with_report::<Value>().expect("Failed to deserialize");
+        assert!(unused.is_empty());
+    }
+
+    #[test]
+    fn aggregate_errors() {
+        //* Given
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        enum Mode {
+            Slow,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Database {
+            host: String,
+            port: u16,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Cache {
+            url: String,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Config {
+            name: String,
+            port: u16,
+            debug: bool,
+            database: Database,
+            cache: Cache,
+            mode: Mode,
+            tags: Vec<u8>,
+        }
+
+        let env = "PORT=eighty\nDEBUG=maybe\nDATABASE_HOST=localhost\nMODE=Fast\nTAGS=1,x";
+
+        //* When
+        let error = from_str::<Config>(env).expect_err("Expected errors");
+
+        //* Then
+        let Error::Aggregate { errors } = error else {
+            panic!("Unexpected error: {error}");
+        };
+        let described: Vec<(&str, Option<&str>)> = errors
+            .iter()
+            .map(|error| match error {
+                Error::MissingKey { key } => (key.as_str(), None),
+                Error::InvalidValue { key, expected, .. } => (key.as_str(), Some(*expected)),
+                e => panic!("Unexpected error: {e}"),
+            })
+            .collect();
+        assert_eq!(
+            described,
+            vec![
+                ("DATABASE_PORT", None),
+                ("NAME", None),
+                ("CACHE_URL", None),
+                ("PORT", Some("u16")),
+                ("DEBUG", Some("bool")),
+                ("MODE", Some("Mode")),
+                ("TAGS", Some("u8")),
+            ]
+        );
+    }
+
+    #[test]
+    fn aggregate_errors_after_rejected_value() {
+        //* Given
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Config {
+            name: String,
+            ip: std::net::IpAddr,
+            port: u16,
+        }
+
+        //* When
+        let error = from_str::<Config>("IP=nope\nPORT=x").expect_err("Expected errors");
+
+        //* Then
+        let Error::Aggregate { errors } = error else {
+            panic!("Unexpected error: {error}");
+        };
+        let described: Vec<(&str, Option<&str>)> = errors
+            .iter()
+            .map(|error| match error {
+                Error::MissingKey { key } => (key.as_str(), None),
+                Error::InvalidValue { key, value, .. } => (key.as_str(), Some(value.as_str())),
+                e => panic!("Unexpected error: {e}"),
+            })
+            .collect();
+        assert_eq!(
+            described,
+            vec![("NAME", None), ("IP", Some("nope")), ("PORT", Some("x"))]
+        );
+    }
+
+    #[test]
+    fn deserialize_indexed_sequences() {
+        //* Given
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Upstream {
+            host: String,
+            port: u16,
+        }
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Config {
+            upstreams: Vec<Upstream>,
+            groups: HashMap<String, Vec<Upstream>>,
+            ports: Vec<u16>,
+        }
+
+        let input = "UPSTREAMS_2_HOST=c\nUPSTREAMS_2_PORT=3\nUPSTREAMS_0_HOST=a\nUPSTREAMS_0_PORT=1\n\
+            UPSTREAMS_1_HOST=b\nUPSTREAMS_1_PORT=2\nGROUPS_BACKUP_SITE_0_HOST=d\n\
+            GROUPS_BACKUP_SITE_0_PORT=4\nPORTS=80,443";
+
+        //* When
+        let config: Config = from_str(input).expect("Failed to deserialize indexed sequences");
+
+        //* Then
+        let hosts: Vec<&str> = config.upstreams.iter().map(|u| u.host.as_str()).collect();
+        assert_eq!(hosts, vec!["a", "b", "c"]);
+        assert_eq!(
+            config.groups.get("backup_site"),
+            Some(&vec![Upstream {
+                host: "d".into(),
+                port: 4
+            }])
+        );
+        assert_eq!(config.ports, vec![80, 443]);
+    }
+
+    #[test]
+    fn deserialize_indexed_sequences_with_gaps() {
+        //* Given
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Upstream {
+            host: String,
+        }
+
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Optional {
+            upstreams: Vec<Option<Upstream>>,
+        }
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Required {
+            upstreams: Vec<Upstream>,
+        }
+
+        let input = "UPSTREAMS_0_HOST=a\nUPSTREAMS_2_HOST=c";
+
+        //* When
+        let optional = from_str::<Optional>(input).expect("Failed to deserialize");
+        let required = from_str::<Required>(input);
+
+        //* Then
+        assert_eq!(
+            optional.upstreams,
+            vec![
+                Some(Upstream { host: "a".into() }),
+                None,
+                Some(Upstream { host: "c".into() })
+            ]
+        );
+        assert!(matches!(required, Err(Error::MissingKey { key }) if key == "UPSTREAMS_1_HOST"));
+
+        // Omitted elements keep their index when written and read back
+        let mut serializer = Serializer::builder()
+            .indexed_sequences(true)
+            .none_style(NoneStyle::Omit)
+            .build(Vec::new());
+        optional
+            .serialize(&mut serializer)
+            .expect("Failed to serialize");
+        let output = String::from_utf8(serializer.into_inner()).expect("Invalid UTF-8");
+        assert_eq!(output, "UPSTREAMS_0_HOST=\"a\"\nUPSTREAMS_2_HOST=\"c\"");
+        assert_eq!(from_str::<Optional>(&output).unwrap(), optional);
+    }
+
+    #[test]
+    fn deserialize_with_separator() {
+        //* Given
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Database {
+            url: String,
+        }
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Upstream {
+            host: String,
+        }
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Config {
+            database: Database,
+            database_url: String,
+            upstreams: Vec<Upstream>,
+        }
+
+        let input = "APP__DATABASE__URL=db\nAPP__DATABASE_URL=local\nAPP__UPSTREAMS__0__HOST=a";
+
+        //* When
+        let mut deserializer = Deserializer::from_str(input)
+            .expect("Failed to parse")
+            .separator("__")
+            .prefix("APP__");
+        let config = Config::deserialize(&mut deserializer).expect("Failed to deserialize");
+
+        //* Then
+        assert_eq!(
+            config,
+            Config {
+                database: Database { url: "db".into() },
+                database_url: "local".into(),
+                upstreams: vec![Upstream { host: "a".into() }],
+            }
+        );
+    }
+
+    #[test]
+    fn prefix_before_key_case() {
+        //* Given
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Config {
+            port: u16,
+        }
+
+        //* When
+        let mut deserializer = Deserializer::from_str("app_port=8080\nAPP_PORT=80")
+            .expect("Failed to parse")
+            .case_sensitive(true)
+            .prefix("app_")
+            .key_case(KeyCase::Preserve);
+        let config = Config::deserialize(&mut deserializer).expect("Failed to deserialize");
+
+        //* Then
+        assert_eq!(config, Config { port: 8080 });
+    }
+
+    #[test]
+    fn deserialize_with_interpolation() {
+        //* Given
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Config {
+            host: String,
+            url: String,
+        }
+
+        let input = "HOST=localhost\nURL=http://${HOST}";
+
+        //* When
+        let mut deserializer =
+            Deserializer::from_str_with(input, &Interpolation::Disabled).expect("Failed to parse");
+        let config = Config::deserialize(&mut deserializer).expect("Failed to deserialize");
+        let unset = Deserializer::from_str_with("S=${UNSET:?x}", &Interpolation::Disabled);
+
+        //* Then
+        assert_eq!(config.url, "http://${HOST}");
+        assert!(unset.is_ok());
+        assert!(matches!(
+            Deserializer::from_str("S=${UNSET:?x}"),
+            Err(Error::UnsetVariable { .. })
+        ));
     }
 }
@@ -0,0 +1,561 @@
+use std::{
+    fmt::{self, Display},
+    fs,
+    ops::Range,
+    path::Path,
+    str::FromStr,
+};
+
+use super::{
+    error::{Error, Result},
+    parse::{SyntaxError, scan, unquote, unquote_literal, validate_key},
+    ser::{double_quote, is_unquoted},
+};
+
+/// Envfile that can be edited without losing its formatting.
+///
+/// Comments, blank lines, the order of the variables, `export` prefixes and
+/// the quoting style of values are kept as written. Lines that are not edited
+/// are written back byte for byte.
+///
+/// # Example
+///
+/// ```
+/// use serde_envfile::{EnvDocument, Error};
+///
+/// fn edit_example() -> Result<(), Error> {
+///     let mut document: EnvDocument = "# Database\nexport HOST='localhost' # local\n".parse()?;
+///
+///     document.set("HOST", "db.internal")?;
+///     document.insert_after("HOST", "PORT", "5432")?;
+///
+///     assert_eq!(
+///         document.to_string(),
+///         "# Database\nexport HOST='db.internal' # local\nPORT=5432\n"
+///     );
+///
+///     Ok(())
+/// }
+/// # edit_example().unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvDocument {
+    lines: Vec<Line>,
+}
+
+/// A statement of the document together with its original text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Line {
+    text: String,
+    assignment: Option<Assignment>,
+}
+
+/// Byte ranges of an assignment relative to the text of its [`Line`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Assignment {
+    key: Range<usize>,
+    value: Range<usize>,
+}
+
+impl Line {
+    fn key(&self) -> Option<&str> {
+        self.assignment.as_ref().map(|a| &self.text[a.key.clone()])
+    }
+
+    fn raw_value(&self) -> Option<&str> {
+        self.assignment
+            .as_ref()
+            .map(|a| &self.text[a.value.clone()])
+    }
+
+    fn is_comment(&self) -> bool {
+        self.assignment.is_none() && self.text.trim_start().starts_with('#')
+    }
+
+    fn ends_with_newline(&self) -> bool {
+        self.text.ends_with('\n')
+    }
+}
+
+impl EnvDocument {
+    /// Create an empty [`EnvDocument`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read an [`EnvDocument`] from a file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let input = fs::read_to_string(path).map_err(|e| Error::io(Some(path.into()), e))?;
+        Self::parse(&input, Some(path))
+    }
+
+    /// Write the [`EnvDocument`] to a file.
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_string()).map_err(|e| Error::io(Some(path.into()), e))
+    }
+
+    fn parse(input: &str, path: Option<&Path>) -> Result<Self> {
+        let statements = scan(input).map_err(|e| e.into_error(input, path))?;
+        let mut lines = Vec::with_capacity(statements.len() + 1);
+
+        if let Some(first) = statements.first().filter(|s| s.span.start > 0) {
+            lines.push(Line {
+                text: input[..first.span.start].into(),
+                assignment: None,
+            });
+        }
+
+        for statement in statements {
+            let start = statement.span.start;
+            let assignment = match statement.assignment {
+                Some(assignment) => {
+                    // Reject values that could not be evaluated later on
+                    unquote_literal(&input[assignment.value.clone()])
+                        .map_err(|e| e.shift(assignment.value.start).into_error(input, path))?;
+
+                    Some(Assignment {
+                        key: assignment.key.start - start..assignment.key.end - start,
+                        value: assignment.value.start - start..assignment.value.end - start,
+                    })
+                }
+                None => None,
+            };
+
+            lines.push(Line {
+                text: input[statement.span].into(),
+                assignment,
+            });
+        }
+
+        Ok(Self { lines })
+    }
+
+    /// Returns `true` if the document defines `key`.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.position(key).is_some()
+    }
+
+    /// Iterate over the defined keys in the order of the document.
+    ///
+    /// Keys that are defined several times are yielded for each definition.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().filter_map(Line::key)
+    }
+
+    /// Returns the evaluated value of `key`.
+    ///
+    /// Quotes and escapes are resolved and variables are substituted with
+    /// earlier definitions of the document. If a key is defined several times,
+    /// the last definition wins. Fails if a substitution can not be evaluated,
+    /// e.g. because a required variable is not set.
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        let Some(index) = self.position(key) else {
+            return Ok(None);
+        };
+
+        self.evaluate(index).map(Some).map_err(|(index, e)| {
+            let offset: usize = self.lines[..index].iter().map(|line| line.text.len()).sum();
+            let value = &self.lines[index]
+                .assignment
+                .as_ref()
+                .expect("line has a key")
+                .value;
+            e.shift(offset + value.start)
+                .into_error(&self.to_string(), None)
+        })
+    }
+
+    /// Evaluate the value of the line at `index`, substituting variables lazily.
+    ///
+    /// Returns the index of the line that failed to evaluate along with the error.
+    fn evaluate(&self, index: usize) -> std::result::Result<String, (usize, SyntaxError)> {
+        let raw = self.lines[index].raw_value().expect("line has a key");
+        let mut failed = None;
+
+        let value = unquote(raw, |name| {
+            let position = self.lines[..index]
+                .iter()
+                .rposition(|line| line.key() == Some(name))?;
+            self.evaluate(position)
+                .map_err(|e| failed.get_or_insert(e))
+                .ok()
+        })
+        .map_err(|e| (index, e))?;
+
+        match failed {
+            Some(failed) => Err(failed),
+            None => Ok(value),
+        }
+    }
+
+    /// Set the value of `key`.
+    ///
+    /// An existing definition keeps its position, `export` prefix, trailing
+    /// comment and, where the value allows it, its quoting style. Otherwise,
+    /// the variable is appended to the end of the document.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        validate_key(key)?;
+
+        match self.position(key) {
+            Some(index) => {
+                self.replace_value(index, value);
+            }
+            None => {
+                let index = self.lines.len();
+                self.insert_line(index, key, value);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Insert `key` on the line after the last definition of `after`.
+    ///
+    /// If `key` is already defined, its value is updated in place instead.
+    /// Fails with [`Error::MissingKey`] if `after` is not defined.
+    pub fn insert_after(&mut self, after: &str, key: &str, value: &str) -> Result<()> {
+        validate_key(key)?;
+
+        if let Some(index) = self.position(key) {
+            self.replace_value(index, value);
+            return Ok(());
+        }
+
+        let index = self
+            .position(after)
+            .ok_or_else(|| Error::MissingKey { key: after.into() })?;
+        self.insert_line(index + 1, key, value);
+
+        Ok(())
+    }
+
+    /// Remove every definition of `key` and return whether it was defined.
+    ///
+    /// Comments are kept, use [`set_comment`](Self::set_comment) beforehand to
+    /// remove the comment of a key.
+    pub fn remove(&mut self, key: &str) -> bool {
+        let mut removed = false;
+
+        while let Some(index) = self.position(key) {
+            self.lines.remove(index);
+            removed = true;
+        }
+
+        removed
+    }
+
+    /// Rename every definition of `from` to `to` and return whether `from` was
+    /// defined.
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<bool> {
+        validate_key(to)?;
+
+        let mut renamed = false;
+        for line in &mut self.lines {
+            if line.key() != Some(from) {
+                continue;
+            }
+
+            let assignment = line.assignment.as_mut().expect("line has a key");
+            line.text.replace_range(assignment.key.clone(), to);
+            let shift = to.len() as isize - from.len() as isize;
+            assignment.key.end = assignment.key.start + to.len();
+            assignment.value = assignment.value.start.wrapping_add_signed(shift)
+                ..assignment.value.end.wrapping_add_signed(shift);
+            renamed = true;
+        }
+
+        Ok(renamed)
+    }
+
+    /// Returns the comment block directly above the last definition of `key`,
+    /// without the leading `#`.
+    pub fn comment(&self, key: &str) -> Option<String> {
+        let index = self.position(key)?;
+        let start = self.comment_start(index);
+        if start == index {
+            return None;
+        }
+
+        let lines: Vec<&str> = self.lines[start..index]
+            .iter()
+            .map(|line| {
+                let text = line.text.trim().trim_start_matches('#');
+                text.strip_prefix(' ').unwrap_or(text)
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    /// Replace the comment block directly above the last definition of `key`.
+    ///
+    /// `None` removes the comment. Returns whether `key` is defined.
+    pub fn set_comment(&mut self, key: &str, comment: Option<&str>) -> bool {
+        let Some(index) = self.position(key) else {
+            return false;
+        };
+
+        let start = self.comment_start(index);
+        let line_ending = self.line_ending();
+        let lines = comment.into_iter().flat_map(str::lines).map(|line| Line {
+            text: match line {
+                "" => format!("#{line_ending}"),
+                line => format!("# {line}{line_ending}"),
+            },
+            assignment: None,
+        });
+        self.lines.splice(start..index, lines);
+
+        true
+    }
+
+    /// Index of the last definition of `key`.
+    fn position(&self, key: &str) -> Option<usize> {
+        self.lines.iter().rposition(|line| line.key() == Some(key))
+    }
+
+    /// Index of the first comment line of the block directly above `index`.
+    fn comment_start(&self, index: usize) -> usize {
+        let mut start = index;
+        while start > 0 && self.lines[start - 1].is_comment() {
+            start -= 1;
+        }
+        start
+    }
+
+    /// Line ending used by the document, `\n` unless it uses `\r\n`.
+    fn line_ending(&self) -> &'static str {
+        match self.lines.iter().find(|line| line.ends_with_newline()) {
+            Some(line) if line.text.ends_with("\r\n") => "\r\n",
+            _ => "\n",
+        }
+    }
+
+    fn replace_value(&mut self, index: usize, value: &str) {
+        let line = &mut self.lines[index];
+        let assignment = line.assignment.as_mut().expect("line has a key");
+        let raw = &line.text[assignment.value.clone()];
+
+        let quoted = quote(value, raw.chars().next());
+        // Keep a trailing comment apart from the value
+        let separator = match raw.is_empty() && line.text[assignment.value.end..].starts_with('#') {
+            true => " ",
+            false => "",
+        };
+
+        line.text
+            .replace_range(assignment.value.clone(), &format!("{quoted}{separator}"));
+        assignment.value.end = assignment.value.start + quoted.len();
+    }
+
+    fn insert_line(&mut self, index: usize, key: &str, value: &str) {
+        let line_ending = self.line_ending();
+        if let Some(previous) = index.checked_sub(1).map(|i| &mut self.lines[i])
+            && !previous.ends_with_newline()
+        {
+            previous.text.push_str(line_ending);
+        }
+
+        let value = quote(value, None);
+        self.lines.insert(
+            index,
+            Line {
+                text: format!("{key}={value}{line_ending}"),
+                assignment: Some(Assignment {
+                    key: 0..key.len(),
+                    value: key.len() + 1..key.len() + 1 + value.len(),
+                }),
+            },
+        );
+    }
+}
+
+/// Quote `value` in the style indicated by the first character of the previous
+/// raw value, falling back to the closest style able to represent it.
+fn quote(value: &str, style: Option<char>) -> String {
+    match style {
+        Some('\'') if !value.contains('\'') => format!("'{value}'"),
+        Some('"') => double_quote(value, true),
+        _ if is_unquoted(value) => value.into(),
+        _ => double_quote(value, true),
+    }
+}
+
+impl FromStr for EnvDocument {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s, None)
+    }
+}
+
+impl Display for EnvDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.lines
+            .iter()
+            .try_for_each(|line| f.write_str(&line.text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use tempfile::NamedTempFile;
+
+    use std::collections::BTreeMap;
+
+    use serde::Serialize as _;
+
+    use super::EnvDocument;
+    use crate::{Error, Quote, Serializer};
+
+    const INPUT: &str = "\u{feff}# Service\r\n\r\nexport HOST='localhost' # local\r\nPORT=8080\r\nNAME=\"my \\\"app\\\"\"\r\nEMPTY=   # unset\r\nURL=http://${HOST}:$PORT";
+
+    #[test]
+    fn untouched_document_is_identical() {
+        //* Given
+        let document: EnvDocument = INPUT.parse().expect("Failed to parse");
+
+        //* When
+        let output = document.to_string();
+
+        //* Then
+        assert_eq!(output, INPUT);
+        assert_eq!(
+            document.keys().collect::<Vec<_>>(),
+            vec!["HOST", "PORT", "NAME", "EMPTY", "URL"]
+        );
+        assert_eq!(document.get("NAME").unwrap().as_deref(), Some("my \"app\""));
+        assert_eq!(
+            document.get("URL").unwrap().as_deref(),
+            Some("http://localhost:8080")
+        );
+        assert_eq!(document.get("MISSING").unwrap(), None);
+    }
+
+    #[test]
+    fn edit_document() {
+        //* Given
+        let mut document: EnvDocument = INPUT.parse().expect("Failed to parse");
+
+        //* When
+        document.set("HOST", "db.internal").unwrap();
+        document.set("EMPTY", "set").unwrap();
+        document.set("NAME", "plain").unwrap();
+        document.insert_after("PORT", "TIMEOUT", "30 s").unwrap();
+        document.set("NEW", "1").unwrap();
+        assert!(document.rename("PORT", "HTTP_PORT").unwrap());
+        assert!(document.remove("URL"));
+
+        //* Then
+        assert_eq!(
+            document.to_string(),
+            "\u{feff}# Service\r\n\r\nexport HOST='db.internal' # local\r\nHTTP_PORT=8080\r\nTIMEOUT=\"30 s\"\r\nNAME=\"plain\"\r\nEMPTY=   set # unset\r\nNEW=1\r\n"
+        );
+        assert_eq!(document.get("EMPTY").unwrap().as_deref(), Some("set"));
+        assert!(!document.contains_key("PORT"));
+
+        let reparsed: EnvDocument = document.to_string().parse().unwrap();
+        assert_eq!(reparsed, document);
+    }
+
+    #[test]
+    fn quote_like_serializer() {
+        //* Given
+        let values = [
+            "plain",
+            "two words",
+            "a#b",
+            "#c",
+            "$HOME",
+            "\"q\"",
+            "a\\b",
+            "x\ny",
+            "é",
+            "",
+        ];
+
+        for value in values {
+            //* When
+            let mut document = EnvDocument::new();
+            document.set("KEY", value).unwrap();
+
+            let mut serializer = Serializer::builder().quote(Quote::Auto).build(Vec::new());
+            BTreeMap::from([("KEY", value)])
+                .serialize(&mut serializer)
+                .expect("Failed to serialize");
+            let serialized = String::from_utf8(serializer.into_inner()).unwrap();
+
+            //* Then
+            assert_eq!(document.to_string().trim_end(), serialized, "{value:?}");
+            assert_eq!(document.get("KEY").unwrap().as_deref(), Some(value));
+        }
+    }
+
+    #[test]
+    fn edit_comments() {
+        //* Given
+        let mut document: EnvDocument =
+            "# Hostname\n# of the service\nHOST=localhost\n\nPORT=8080\n"
+                .parse()
+                .unwrap();
+
+        //* When
+        let host = document.comment("HOST");
+        let port = document.comment("PORT");
+        document.set_comment("HOST", None);
+        document.set_comment("PORT", Some("Listening port"));
+
+        //* Then
+        assert_eq!(host.as_deref(), Some("Hostname\nof the service"));
+        assert_eq!(port, None);
+        assert_eq!(
+            document.to_string(),
+            "HOST=localhost\n\n# Listening port\nPORT=8080\n"
+        );
+
+        assert!(document.remove("PORT"));
+        assert_eq!(document.to_string(), "HOST=localhost\n\n# Listening port\n");
+    }
+
+    #[test]
+    fn edit_errors() {
+        //* Given
+        let mut document = EnvDocument::new();
+
+        //* When
+        let invalid = document.set("NOT VALID", "1");
+        let missing = document.insert_after("MISSING", "KEY", "1");
+        let syntax = "KEY=\"\\q\"".parse::<EnvDocument>();
+        let unset = "A=1\nB=${C:?required}"
+            .parse::<EnvDocument>()
+            .unwrap()
+            .get("B");
+
+        //* Then
+        assert!(matches!(invalid, Err(Error::InvalidKey { .. })));
+        assert!(matches!(missing, Err(Error::MissingKey { key }) if key == "MISSING"));
+        assert!(matches!(syntax, Err(Error::Syntax { .. })));
+        assert!(
+            matches!(unset, Err(Error::UnsetVariable { name, location, .. }) if name == "C" && location.line == 2)
+        );
+    }
+
+    #[test]
+    fn document_file_round_trip() {
+        //* Given
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), INPUT).unwrap();
+
+        //* When
+        let mut document = EnvDocument::from_file(file.path()).expect("Failed to read");
+        document.set("PORT", "9090").unwrap();
+        document.to_file(file.path()).expect("Failed to write");
+
+        //* Then
+        let output = read_to_string(file.path()).unwrap();
+        assert_eq!(output, INPUT.replace("PORT=8080", "PORT=9090"));
+    }
+}
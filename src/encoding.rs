@@ -0,0 +1,182 @@
+use std::{borrow::Cow, fmt::Write as _};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64_URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encoding of byte strings, such as those of `serde_bytes`, in values.
+///
+/// Sequences of bytes such as `Vec<u8>` are sequences of numbers to serde and
+/// are therefore written as a list instead.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ByteEncoding {
+    /// Write bytes as they are, which fails for bytes that are not valid UTF-8.
+    #[default]
+    Utf8,
+    /// Standard base64 with padding, as defined in RFC 4648.
+    Base64,
+    /// URL-safe base64 using `-` and `_`, without padding. Padding is accepted
+    /// when deserializing.
+    Base64Url,
+    /// Lowercase hexadecimal with two digits per byte. Uppercase digits are
+    /// accepted when deserializing.
+    Hex,
+}
+
+/// Reason why a value could not be decoded.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub(crate) struct DecodeError(&'static str);
+
+impl ByteEncoding {
+    /// Returns the name of the encoding as expected in error messages.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ByteEncoding::Utf8 => "UTF-8",
+            ByteEncoding::Base64 => "base64",
+            ByteEncoding::Base64Url => "URL-safe base64",
+            ByteEncoding::Hex => "hex",
+        }
+    }
+
+    /// Encode `bytes`, returning `None` if they can not be represented.
+    pub(crate) fn encode(self, bytes: &[u8]) -> Option<Cow<'_, str>> {
+        match self {
+            ByteEncoding::Utf8 => std::str::from_utf8(bytes).ok().map(Cow::Borrowed),
+            ByteEncoding::Base64 => Some(Cow::Owned(encode_base64(bytes, BASE64, true))),
+            ByteEncoding::Base64Url => Some(Cow::Owned(encode_base64(bytes, BASE64_URL, false))),
+            ByteEncoding::Hex => Some(Cow::Owned(bytes.iter().fold(
+                String::with_capacity(bytes.len() * 2),
+                |mut output, byte| {
+                    write!(output, "{byte:02x}").expect("writing to a string does not fail");
+                    output
+                },
+            ))),
+        }
+    }
+
+    /// Decode `value`, borrowing it if its bytes are used as they are.
+    pub(crate) fn decode(self, value: &str) -> Result<Cow<'_, [u8]>, DecodeError> {
+        match self {
+            ByteEncoding::Utf8 => Ok(Cow::Borrowed(value.as_bytes())),
+            ByteEncoding::Base64 => decode_base64(value, BASE64).map(Cow::Owned),
+            ByteEncoding::Base64Url => decode_base64(value, BASE64_URL).map(Cow::Owned),
+            ByteEncoding::Hex => decode_hex(value).map(Cow::Owned),
+        }
+    }
+}
+
+fn encode_base64(bytes: &[u8], alphabet: &[u8; 64], padding: bool) -> String {
+    let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (index, &byte)| {
+                group | u32::from(byte) << (16 - 8 * index)
+            });
+
+        // Every byte spreads over two digits, the following ones over one more each
+        for index in 0..4 {
+            if index <= chunk.len() {
+                output.push(alphabet[(group >> (18 - 6 * index)) as usize & 0x3f] as char);
+            } else if padding {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+fn decode_base64(value: &str, alphabet: &[u8; 64]) -> Result<Vec<u8>, DecodeError> {
+    let digits = value.trim_end_matches('=');
+    if value.len() - digits.len() > 2 || digits.len() % 4 == 1 {
+        return Err(DecodeError("invalid base64 length"));
+    }
+
+    let mut output = Vec::with_capacity(digits.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for digit in digits.bytes() {
+        let Some(value) = alphabet.iter().position(|&c| c == digit) else {
+            return Err(DecodeError("invalid base64 digit"));
+        };
+        buffer = buffer << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Ok(output)
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, DecodeError> {
+    if value.len() % 2 == 1 {
+        return Err(DecodeError("odd number of hex digits"));
+    }
+
+    value
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let digit = |c: u8| char::from(c).to_digit(16);
+            match (digit(pair[0]), digit(pair[1])) {
+                (Some(high), Some(low)) => Ok((high << 4 | low) as u8),
+                _ => Err(DecodeError("invalid hex digit")),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ByteEncoding;
+
+    #[test]
+    fn encode_and_decode_bytes() {
+        //* Given
+        let bytes: &[&[u8]] = &[b"", b"f", b"fo", b"foo", b"foob", b"fooba", b"foobar"];
+
+        //* When
+        let encoded = |encoding: ByteEncoding| {
+            bytes
+                .iter()
+                .map(|bytes| encoding.encode(bytes).expect("Failed to encode"))
+                .collect::<Vec<_>>()
+        };
+
+        //* Then
+        let base64 = [
+            "", "Zg==", "Zm8=", "Zm9v", "Zm9vYg==", "Zm9vYmE=", "Zm9vYmFy",
+        ];
+        assert_eq!(encoded(ByteEncoding::Base64), base64);
+        assert_eq!(encoded(ByteEncoding::Hex)[6], "666f6f626172");
+        assert_eq!(
+            ByteEncoding::Base64Url.encode(&[0xfb, 0xff]).unwrap(),
+            "-_8"
+        );
+
+        for encoding in [
+            ByteEncoding::Base64,
+            ByteEncoding::Base64Url,
+            ByteEncoding::Hex,
+        ] {
+            for (bytes, encoded) in bytes.iter().zip(encoded(encoding)) {
+                assert_eq!(
+                    &*encoding.decode(&encoded).expect("Failed to decode"),
+                    *bytes
+                );
+            }
+        }
+        assert_eq!(
+            &*ByteEncoding::Base64Url.decode("-_8=").unwrap(),
+            [0xfb, 0xff]
+        );
+        assert_eq!(&*ByteEncoding::Hex.decode("FBff").unwrap(), [0xfb, 0xff]);
+        assert!(ByteEncoding::Base64.decode("Zm9v!").is_err());
+        assert!(ByteEncoding::Base64.decode("Z").is_err());
+        assert!(ByteEncoding::Hex.decode("abc").is_err());
+        assert!(ByteEncoding::Hex.decode("zz").is_err());
+        assert!(ByteEncoding::Utf8.encode(&[0xff]).is_none());
+    }
+}
@@ -1,29 +1,102 @@
-use std::fmt::Display;
+use std::{fmt::Display, io, path::PathBuf};
 
 use serde::{de, ser};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Position of a variable in its input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// Path of the file the variable was read from, if any.
+    pub path: Option<PathBuf>,
+    /// Line number, starting at 1.
+    pub line: usize,
+    /// Column number in characters, starting at 1.
+    pub column: usize,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}:{}:{}", path.display(), self.line, self.column),
+            None => write!(f, "line {}, column {}", self.line, self.column),
+        }
+    }
+}
+
 /// Possible errors.
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("{0}")]
     Message(String),
 
-    #[error("Unexpected end of input")]
-    Eof,
-    #[error("Syntax error")]
-    Syntax,
-    #[error("Expected boolean")]
-    ExpectedBoolean,
-    #[error("Expected integer")]
-    ExpectedInteger,
-    #[error("Tuple structs are not supported")]
+    #[error("Failed to read or write {}: {source}", describe_path(.path))]
+    Io {
+        path: Option<PathBuf>,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Syntax error at {location}: {message}")]
+    Syntax { location: Location, message: String },
+    #[error("Required variable {name} is not set at {location}{}", describe_message(.message))]
+    UnsetVariable {
+        name: String,
+        message: String,
+        location: Location,
+    },
+    #[error("Invalid value {value:?} for {key}{}, expected {expected}", describe_location(.location))]
+    InvalidValue {
+        key: String,
+        value: String,
+        expected: &'static str,
+        location: Option<Location>,
+        #[source]
+        source: Option<Box<dyn std::error::Error + Send + Sync>>,
+    },
+    #[error("Unused keys: {}", .keys.join(", "))]
+    UnusedKeys { keys: Vec<String> },
+    #[error("Missing value for {key}")]
+    MissingKey { key: String },
+    #[error("Invalid key {key:?}")]
+    InvalidKey { key: String },
+    #[error("Value of {key} can not be represented: {reason}")]
+    Unrepresentable { key: String, reason: &'static str },
+    #[error("{} errors occurred:{}", .errors.len(), describe_errors(.errors))]
+    Aggregate { errors: Vec<Error> },
+    #[error("Tuple structs are only supported nested in a struct or map")]
     UnsupportedTupleStruct,
     #[error("Unsupported structure in sequence")]
     UnsupportedStructureInSeq,
 }
 
+fn describe_path(path: &Option<PathBuf>) -> String {
+    match path {
+        Some(path) => path.display().to_string(),
+        None => "input".into(),
+    }
+}
+
+fn describe_errors(errors: &[Error]) -> String {
+    errors
+        .iter()
+        .map(|error| format!("\n  - {error}"))
+        .collect()
+}
+
+fn describe_message(message: &str) -> String {
+    match message {
+        "" => String::new(),
+        message => format!(": {message}"),
+    }
+}
+
+fn describe_location(location: &Option<Location>) -> String {
+    match location {
+        Some(location) => format!(" at {location}"),
+        None => String::new(),
+    }
+}
+
 impl ser::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
         Error::Message(msg.to_string())
@@ -34,6 +107,12 @@ impl de::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
         Error::Message(msg.to_string())
     }
+
+    fn missing_field(field: &'static str) -> Self {
+        Error::MissingKey {
+            key: field.to_uppercase(),
+        }
+    }
 }
 
 impl Error {
@@ -43,4 +122,8 @@ impl Error {
     {
         Self::Message(e.to_string())
     }
-}
\ No newline at end of file
+
+    pub(crate) fn io(path: Option<PathBuf>, source: io::Error) -> Self {
+        Self::Io { path, source }
+    }
+}
@@ -1,14 +1,14 @@
 //! # serde-envfile
-//! Built ontop the [`dotenvy`](https://github.com/allan2/dotenvy) and
-//! [`envy`](https://github.com/softprops/envy) crates, `serde-envfile`
-//! supports both the serialization and the deserialization of environment
-//! variables from or to files (`from_file`, `to_file`), strings
-//! (`from_str`, `to_string`), or the environment of the application
+//! `serde-envfile` supports both the serialization and the deserialization
+//! of environment variables from or to files (`from_file`, `to_file`),
+//! strings (`from_str`, `to_string`), or the environment of the application
 //! (`from_env`).
 //!
 //! ## Examples
 //! Note that keys are transformed to lowercase during deserialization.
-//! With serialization, the contrary is the case.
+//! With serialization, the contrary is the case. Nested structs and maps are
+//! flattened into underscore-joined keys such as `DATABASE_HOST`, or joined
+//! with a custom separator such as `__` to tell them apart from field names.
 //! ```no_run
 //! use serde::{Deserialize, Serialize};
 //! use serde_envfile::{Error, from_str, to_string};
@@ -29,6 +29,40 @@
 //! }
 //! ```
 //!
+//! Enums are written as their variant name (`MODE=Off`), or with the variant
+//! name as a key segment if the variant holds data (`STORAGE_S3_BUCKET=assets`).
+//! Internally tagged enums store the variant in their tag next to its fields
+//! (`STORAGE_KIND=s3`, `STORAGE_BUCKET=assets`) and adjacently tagged enums
+//! nest the fields below the content key (`STORAGE_OPTIONS_BUCKET=assets`).
+//! Fields of internally tagged and untagged enums are read before their types
+//! are known, so values that look like booleans or numbers can't be read into
+//! strings; use adjacent tagging for those.
+//! ```no_run
+//! use serde::Deserialize;
+//! use serde_envfile::{Error, from_str};
+//!
+//! #[derive(Debug, Deserialize)]
+//! #[serde(tag = "kind", rename_all = "lowercase")]
+//! enum Storage {
+//!     S3 { bucket: String, port: u16 },
+//!     Local { path: String },
+//! }
+//!
+//! #[derive(Debug, Deserialize)]
+//! struct Config {
+//!     storage: Storage,
+//! }
+//!
+//! fn main() -> Result<(), Error> {
+//!     let env = "STORAGE_KIND=s3\nSTORAGE_BUCKET=assets\nSTORAGE_PORT=9000";
+//!     let config: Config = from_str(env)?;
+//!
+//!     println!("{:?}", config);
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
 //! Introducing the `Value` type, `serde-envfile`, also provides a more flexible approach to working with environment variables.
 //! ```no_run
 //! use serde_envfile::{to_string, Error, Value};
@@ -46,15 +80,33 @@
 
 #[doc(hidden)]
 pub mod de;
+pub(crate) mod document;
+pub(crate) mod encoding;
 pub(crate) mod error;
+pub(crate) mod list;
+pub(crate) mod loader;
+pub(crate) mod parse;
 pub(crate) mod prefixed;
+pub(crate) mod provenance;
 pub(crate) mod ser;
 pub(crate) mod value;
 
-pub use de::{from_env, from_file, from_reader, from_str};
-pub use error::Error;
+pub use de::{
+    Deserializer, from_env, from_env_with_report, from_file, from_file_with_report, from_reader,
+    from_reader_with_report, from_str, from_str_with_report,
+};
+pub use document::EnvDocument;
+pub use encoding::ByteEncoding;
+pub use error::{Error, Location};
+pub use list::Delimiter;
+pub use loader::Loader;
+pub use parse::{Interpolation, Lookup};
 pub use prefixed::{Prefixed, prefixed};
-pub use ser::{Serializer, to_file, to_string, to_writer};
+pub use provenance::{Definition, Origin, Provenance};
+pub use ser::{
+    Compare, KeyCase, KeyOrder, LineEnding, Multiline, NoneStyle, Quote, Serializer,
+    SerializerBuilder, to_file, to_string, to_writer,
+};
 pub use value::Value;
 
 #[cfg(test)]
@@ -0,0 +1,126 @@
+use std::borrow::Cow;
+
+/// Separator between the elements of a sequence stored in a single variable.
+///
+/// Elements containing the delimiter or a backslash have them escaped with a
+/// backslash, so that `vec!["a,b", "c"]` is written as `a\,b,c` and read back
+/// as two elements.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    /// Separate elements with `,`.
+    #[default]
+    Comma,
+    /// Separate elements with `:`, as in `PATH`.
+    Colon,
+    /// Separate elements with `;`.
+    Semicolon,
+    /// Separate elements with a space when serializing and with any run of
+    /// whitespace when deserializing, which skips empty elements.
+    Whitespace,
+}
+
+impl Delimiter {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Delimiter::Comma => ",",
+            Delimiter::Colon => ":",
+            Delimiter::Semicolon => ";",
+            Delimiter::Whitespace => " ",
+        }
+    }
+
+    fn matches(self, c: char) -> bool {
+        match self {
+            Delimiter::Whitespace => c.is_whitespace(),
+            delimiter => delimiter.as_str().starts_with(c),
+        }
+    }
+}
+
+/// Append `element` to `output`, escaping backslashes and delimiters.
+pub(crate) fn escape(element: &str, delimiter: Delimiter, output: &mut String) {
+    for c in element.chars() {
+        if c == '\\' || delimiter.matches(c) {
+            output.push('\\');
+        }
+        output.push(c);
+    }
+}
+
+/// Split `value` into its elements at unescaped delimiters.
+///
+/// Only backslashes followed by a delimiter or another backslash are treated
+/// as escapes, other backslashes such as in `C:\bin` are kept.
+pub(crate) fn split(value: &str, delimiter: Delimiter) -> Vec<Cow<'_, str>> {
+    let mut elements = Vec::new();
+    if value.is_empty() {
+        return elements;
+    }
+
+    let mut start = 0;
+    let mut unescaped: Option<String> = None;
+    let mut chars = value.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        if c == '\\' {
+            if let Some(&(next_index, next)) = chars.peek()
+                && (next == '\\' || delimiter.matches(next))
+            {
+                let element = unescaped.get_or_insert_with(String::new);
+                element.push_str(&value[start..index]);
+                element.push(next);
+                chars.next();
+                start = next_index + next.len_utf8();
+            }
+        } else if delimiter.matches(c) {
+            elements.push(element(&value[start..index], unescaped.take()));
+            start = index + c.len_utf8();
+        }
+    }
+    elements.push(element(&value[start..], unescaped));
+
+    if delimiter == Delimiter::Whitespace {
+        elements.retain(|element| !element.is_empty());
+    }
+    elements
+}
+
+fn element(rest: &str, unescaped: Option<String>) -> Cow<'_, str> {
+    match unescaped {
+        Some(mut element) => {
+            element.push_str(rest);
+            Cow::Owned(element)
+        }
+        None => Cow::Borrowed(rest),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Delimiter, escape, split};
+
+    #[test]
+    fn escape_and_split_elements() {
+        //* Given
+        let elements = ["a,b", "c\\d", "", "e f"];
+
+        //* When
+        let mut output = String::new();
+        for (index, element) in elements.iter().enumerate() {
+            if index > 0 {
+                output.push(',');
+            }
+            escape(element, Delimiter::Comma, &mut output);
+        }
+
+        //* Then
+        assert_eq!(output, "a\\,b,c\\\\d,,e f");
+        assert_eq!(split(&output, Delimiter::Comma), elements);
+        assert_eq!(
+            split("C:\\bin;D:\\bin", Delimiter::Semicolon),
+            ["C:\\bin", "D:\\bin"]
+        );
+        assert_eq!(split(" a  b\\ c\t", Delimiter::Whitespace), ["a", "b c"]);
+        assert!(split("", Delimiter::Colon).is_empty());
+    }
+}
@@ -0,0 +1,461 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    de::Deserializer,
+    encoding::ByteEncoding,
+    error::{Error, Result},
+    list::Delimiter,
+    parse::{Interpolation, parse_with},
+    provenance::{Definition, Origin, Provenance},
+    ser::{KeyCase, NoneStyle},
+};
+
+/// A source of environment variables of a [`Loader`].
+#[derive(Debug, Clone)]
+enum Source {
+    File { path: PathBuf, required: bool },
+    Str(String),
+    Env,
+    Vars(Vec<(String, String)>),
+}
+
+/// Loads environment variables from several layered sources.
+///
+/// Sources are applied in the order they were added, so that a variable of a
+/// later source overrides the same variable of an earlier one. Envfiles may
+/// reference variables of earlier sources.
+///
+/// # Example
+///
+/// ```no_run
+/// use serde::Deserialize;
+/// use serde_envfile::{Error, Loader};
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Config {
+///     database_url: String,
+/// }
+///
+/// fn main() -> Result<(), Error> {
+///     let profile = "production";
+///
+///     let config: Config = Loader::new()
+///         .file(".env")
+///         .optional_file(".env.local")
+///         .optional_file(format!(".env.{profile}"))
+///         .optional_file(format!(".env.{profile}.local"))
+///         .env()
+///         .load()?;
+///
+///     println!("{:?}", config);
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Loader {
+    sources: Vec<Source>,
+    prefix: Option<String>,
+    interpolation: Interpolation,
+    strict: bool,
+    separator: Option<String>,
+    key_case: KeyCase,
+    case_sensitive: bool,
+    delimiter: Delimiter,
+    none_style: NoneStyle,
+    byte_encoding: ByteEncoding,
+}
+
+impl Loader {
+    /// Create a [`Loader`] without any sources.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an envfile that has to exist.
+    pub fn file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.sources.push(Source::File {
+            path: path.into(),
+            required: true,
+        });
+        self
+    }
+
+    /// Add an envfile that is skipped if it does not exist.
+    pub fn optional_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.sources.push(Source::File {
+            path: path.into(),
+            required: false,
+        });
+        self
+    }
+
+    /// Add variables from a string in the envfile format.
+    pub fn str<S: Into<String>>(mut self, input: S) -> Self {
+        self.sources.push(Source::Str(input.into()));
+        self
+    }
+
+    /// Add the environment of the application.
+    ///
+    /// Like other sources, its variables can be referenced by the envfiles
+    /// added after it, even with [`Interpolation::File`].
+    pub fn env(mut self) -> Self {
+        self.sources.push(Source::Env);
+        self
+    }
+
+    /// Add variables from an iterator of key-value pairs.
+    pub fn vars<Iter, K, V>(mut self, iter: Iter) -> Self
+    where
+        Iter: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let vars = iter.into_iter().map(|(k, v)| (k.into(), v.into()));
+        self.sources.push(Source::Vars(vars.collect()));
+        self
+    }
+
+    /// Only consider variables starting with `prefix` and strip it from their keys.
+    pub fn prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Set how variables referenced in envfiles are substituted.
+    ///
+    /// Defaults to [`Interpolation::FileThenEnv`].
+    pub fn interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    /// Fail with [`Error::UnusedKeys`] if keys are left unused by the deserialization.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set the separator joining the key segments of nested structures.
+    /// Defaults to `_`.
+    pub fn separator<S: Into<String>>(mut self, separator: S) -> Self {
+        self.separator = Some(separator.into());
+        self
+    }
+
+    /// Set the case applied to field names and the prefix before they are
+    /// matched against keys. Defaults to [`KeyCase::Upper`].
+    pub fn key_case(mut self, key_case: KeyCase) -> Self {
+        self.key_case = key_case;
+        self
+    }
+
+    /// Match keys case-sensitively and yield map keys exactly as written.
+    /// Defaults to `false`.
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    /// Set the delimiter between the elements of sequences stored in a single
+    /// variable. Defaults to [`Delimiter::Comma`].
+    pub fn delimiter(mut self, delimiter: Delimiter) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Set which values are read as `None`. Defaults to [`NoneStyle::Empty`].
+    pub fn none_style(mut self, none_style: NoneStyle) -> Self {
+        self.none_style = none_style;
+        self
+    }
+
+    /// Set the encoding of byte strings. Defaults to [`ByteEncoding::Utf8`].
+    pub fn byte_encoding(mut self, byte_encoding: ByteEncoding) -> Self {
+        self.byte_encoding = byte_encoding;
+        self
+    }
+
+    /// Merge all sources and deserialize the result into an instance of type `T`.
+    pub fn load<T>(&self) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.deserialize(self.read()?)
+    }
+
+    /// Like [`load`](Self::load), additionally returning where each variable was defined.
+    pub fn load_with_provenance<T>(&self) -> Result<(T, Provenance)>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let definitions = self.read()?;
+
+        let mut provenance = Provenance::default();
+        definitions
+            .iter()
+            .cloned()
+            .for_each(|definition| provenance.push(definition));
+
+        Ok((self.deserialize(definitions)?, provenance))
+    }
+
+    /// Merge all sources into a [`Deserializer`], e.g. to deserialize with
+    /// `serde_path_to_error`.
+    pub fn deserializer(&self) -> Result<Deserializer> {
+        Ok(self.merge(self.read()?))
+    }
+
+    fn deserialize<T>(&self, definitions: Vec<Definition>) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.merge(definitions).deserialize_all()
+    }
+
+    fn merge(&self, definitions: Vec<Definition>) -> Deserializer {
+        let variables = definitions.into_iter().map(|definition| {
+            let location = match definition.origin {
+                Origin::Input(location) => Some(location),
+                Origin::Env | Origin::Vars => None,
+            };
+            (definition.name, definition.value, location)
+        });
+
+        let deserializer = Deserializer::with_locations(self.prefix.as_deref(), variables)
+            .strict(self.strict)
+            .key_case(self.key_case)
+            .case_sensitive(self.case_sensitive)
+            .delimiter(self.delimiter)
+            .none_style(self.none_style.clone())
+            .byte_encoding(self.byte_encoding);
+        match &self.separator {
+            Some(separator) => deserializer.separator(separator),
+            None => deserializer,
+        }
+    }
+
+    /// Read the variables of all sources in order.
+    fn read(&self) -> Result<Vec<Definition>> {
+        let mut defined = HashMap::new();
+        let mut definitions = Vec::new();
+
+        for source in &self.sources {
+            let parsed = match source {
+                Source::File { path, required } => match read_file(path, *required)? {
+                    Some(input) => {
+                        parse_with(&input, Some(path), &mut defined, &self.interpolation)?
+                    }
+                    None => continue,
+                },
+                Source::Str(input) => parse_with(input, None, &mut defined, &self.interpolation)?,
+                Source::Env => {
+                    let vars: Vec<_> = std::env::vars().collect();
+                    defined.extend(vars.iter().cloned());
+                    definitions.extend(vars.into_iter().map(|(name, value)| Definition {
+                        name,
+                        value,
+                        origin: Origin::Env,
+                    }));
+                    continue;
+                }
+                Source::Vars(vars) => {
+                    defined.extend(vars.iter().cloned());
+                    definitions.extend(vars.iter().map(|(name, value)| Definition {
+                        name: name.clone(),
+                        value: value.clone(),
+                        origin: Origin::Vars,
+                    }));
+                    continue;
+                }
+            };
+
+            definitions.extend(parsed.into_iter().map(|variable| Definition {
+                name: variable.name,
+                value: variable.value,
+                origin: Origin::Input(variable.location),
+            }));
+        }
+
+        Ok(definitions)
+    }
+}
+
+fn read_file(path: &Path, required: bool) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(input) => Ok(Some(input)),
+        Err(e) if !required && e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(Error::io(Some(path.into()), e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use tempfile::TempDir;
+
+    use super::Loader;
+    use crate::{Error, Interpolation, Origin, Value, prefixed};
+
+    #[test]
+    fn load_layered_files() {
+        //* Given
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Config {
+            host: String,
+            port: u16,
+            url: String,
+        }
+
+        let dir = TempDir::new().unwrap();
+        write(dir.path().join(".env"), "HOST=localhost\nPORT=8080").unwrap();
+        write(
+            dir.path().join(".env.production"),
+            "HOST=example.com\nURL=http://$HOST:$PORT",
+        )
+        .unwrap();
+
+        //* When
+        let config: Config = Loader::new()
+            .file(dir.path().join(".env"))
+            .optional_file(dir.path().join(".env.local"))
+            .optional_file(dir.path().join(".env.production"))
+            .vars([("PORT", "443")])
+            .load()
+            .expect("Failed to load");
+
+        //* Then
+        assert_eq!(
+            config,
+            Config {
+                host: "example.com".into(),
+                port: 443,
+                url: "http://example.com:8080".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn load_missing_required_file() {
+        //* Given
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(".env");
+
+        //* When
+        let error = Loader::new()
+            .file(&path)
+            .load::<Value>()
+            .expect_err("Expected an I/O error");
+
+        //* Then
+        assert!(matches!(error, Error::Io { path: Some(p), .. } if p == path));
+    }
+
+    #[test]
+    fn load_env_overlay_with_prefix() {
+        //* Given
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Config {
+            path: String,
+            name: String,
+        }
+
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join(".env");
+        write(&file, "PATH=/bin\nAPP_PATH=/bin\nAPP_NAME=app").unwrap();
+
+        //* When
+        let value: Value = Loader::new()
+            .file(&file)
+            .env()
+            .load()
+            .expect("Failed to load");
+        let config: Config = prefixed("APP_")
+            .loader()
+            .file(&file)
+            .env()
+            .vars([("APP_NAME", "service")])
+            .load()
+            .expect("Failed to load");
+
+        //* Then
+        assert_eq!(value.get("path"), std::env::var("PATH").ok().as_ref());
+        assert_eq!(
+            config,
+            Config {
+                path: "/bin".into(),
+                name: "service".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn interpolate_env_source() {
+        //* Given
+        let path = std::env::var("PATH").expect("PATH is not set");
+
+        //* When
+        let value: Value = Loader::new()
+            .interpolation(Interpolation::File)
+            .env()
+            .str("BIN=${PATH}/bin")
+            .load()
+            .expect("Failed to load");
+
+        //* Then
+        assert_eq!(value.get("bin"), Some(&format!("{path}/bin")));
+    }
+
+    #[test]
+    fn load_with_provenance() {
+        //* Given
+        let dir = TempDir::new().unwrap();
+        let base = dir.path().join(".env");
+        let profile = dir.path().join(".env.production");
+        write(
+            &base,
+            "# base\nDATABASE_URL=postgres://localhost\nPORT=8080",
+        )
+        .unwrap();
+        write(&profile, "\nDATABASE_URL=postgres://db.internal").unwrap();
+
+        //* When
+        let (value, provenance) = Loader::new()
+            .file(&base)
+            .file(&profile)
+            .str("PORT=9090")
+            .load_with_provenance::<Value>()
+            .expect("Failed to load");
+
+        //* Then
+        assert_eq!(value.get("database_url").unwrap(), "postgres://db.internal");
+
+        let url = provenance.get("database_url").unwrap();
+        assert_eq!(url.name, "DATABASE_URL");
+        match &url.origin {
+            Origin::Input(location) => {
+                assert_eq!(location.path.as_deref(), Some(profile.as_path()));
+                assert_eq!(location.line, 2);
+            }
+            origin => panic!("Unexpected origin: {origin}"),
+        }
+        let overridden = provenance.overridden("DATABASE_URL");
+        assert_eq!(overridden.len(), 1);
+        assert_eq!(overridden[0].value, "postgres://localhost");
+        assert_eq!(
+            overridden[0].origin.location().unwrap().path.as_deref(),
+            Some(base.as_path())
+        );
+
+        let port = provenance.origin("PORT").unwrap();
+        assert_eq!(port.to_string(), "input at line 1, column 6");
+        assert!(provenance.origin("MISSING").is_none());
+        assert_eq!(provenance.iter().count(), 2);
+    }
+}
@@ -0,0 +1,817 @@
+use std::{collections::HashMap, fmt, ops::Range, path::Path, sync::Arc};
+
+use super::error::{Error, Location, Result};
+
+/// A line of an envfile, or several lines in case of a multi-line value.
+pub(crate) struct Statement {
+    /// Byte range of the statement in the input, including the line ending.
+    pub span: Range<usize>,
+    pub assignment: Option<Assignment>,
+}
+
+/// A variable definition within a [`Statement`].
+pub(crate) struct Assignment {
+    /// Byte range of the key in the input.
+    pub key: Range<usize>,
+    /// Byte range of the raw value in the input, including quotes and escapes.
+    pub value: Range<usize>,
+    /// Line of the value, starting at 1.
+    pub line: usize,
+    /// Column of the value in characters, starting at 1.
+    pub column: usize,
+}
+
+/// A variable with its evaluated value.
+pub(crate) struct Variable {
+    pub name: String,
+    pub value: String,
+    pub location: Location,
+}
+
+/// Syntax error relative to the start of the scanned input.
+#[derive(Debug)]
+pub(crate) struct SyntaxError {
+    pub offset: usize,
+    kind: SyntaxErrorKind,
+}
+
+#[derive(Debug)]
+enum SyntaxErrorKind {
+    Invalid(&'static str),
+    /// A variable required with `${NAME:?message}` or `${NAME?message}` is not set.
+    Unset {
+        name: String,
+        message: String,
+    },
+}
+
+impl SyntaxError {
+    pub(crate) fn new(offset: usize, message: &'static str) -> Self {
+        Self {
+            offset,
+            kind: SyntaxErrorKind::Invalid(message),
+        }
+    }
+
+    fn unset(offset: usize, name: String, message: String) -> Self {
+        Self {
+            offset,
+            kind: SyntaxErrorKind::Unset { name, message },
+        }
+    }
+
+    /// Move the error by `offset` bytes, e.g. to make it relative to an enclosing input.
+    pub(crate) fn shift(mut self, offset: usize) -> Self {
+        self.offset += offset;
+        self
+    }
+
+    pub(crate) fn into_error(self, input: &str, path: Option<&Path>) -> Error {
+        let (line, column) = position(input, self.offset);
+        let location = Location {
+            path: path.map(Into::into),
+            line,
+            column,
+        };
+
+        match self.kind {
+            SyntaxErrorKind::Invalid(message) => Error::Syntax {
+                location,
+                message: message.into(),
+            },
+            SyntaxErrorKind::Unset { name, message } => Error::UnsetVariable {
+                name,
+                message,
+                location,
+            },
+        }
+    }
+}
+
+/// Returns the line and column of a byte offset, both starting at 1.
+pub(crate) fn position(input: &str, offset: usize) -> (usize, usize) {
+    let before = &input[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+/// Function resolving the value of a variable by its name.
+pub type Lookup = dyn Fn(&str) -> Option<String> + Send + Sync;
+
+/// Source of the values substituted for variables referenced with `$NAME` or
+/// `${NAME}` in envfiles.
+///
+/// Unless disabled, earlier definitions of the envfile, or of the envfiles
+/// loaded before it, take precedence over any other source. Variables that
+/// can not be resolved are substituted with an empty string.
+///
+/// The substitution syntax of Docker Compose is supported, including
+/// `${NAME:-default}`, `${NAME-default}`, `${NAME:+alternative}`,
+/// `${NAME+alternative}`, `${NAME:?message}`, `${NAME?message}` and `$$`.
+/// A required variable that is not set results in [`Error::UnsetVariable`].
+///
+/// # Example
+///
+/// ```
+/// use serde_envfile::{Error, Interpolation, Loader, Value};
+///
+/// fn interpolation_example() -> Result<(), Error> {
+///     let value: Value = Loader::new()
+///         .str("URL=http://${HOST}:8080")
+///         .interpolation(Interpolation::map([("HOST", "localhost")]))
+///         .load()?;
+///
+///     assert_eq!(value.get("url").unwrap(), "http://localhost:8080");
+///     Ok(())
+/// }
+/// # interpolation_example().unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub enum Interpolation {
+    /// Keep references to variables as written.
+    Disabled,
+    /// Only substitute earlier definitions.
+    File,
+    /// Substitute earlier definitions or else variables of the environment
+    /// of the application.
+    #[default]
+    FileThenEnv,
+    /// Substitute earlier definitions or else the value returned by the function.
+    Custom(Arc<Lookup>),
+}
+
+impl Interpolation {
+    /// Substitute earlier definitions or else the value returned by `lookup`.
+    pub fn custom<F>(lookup: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        Self::Custom(Arc::new(lookup))
+    }
+
+    /// Substitute earlier definitions or else the value of the given key-value pairs.
+    pub fn map<Iter, K, V>(iter: Iter) -> Self
+    where
+        Iter: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let map: HashMap<String, String> = iter
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect();
+        Self::custom(move |name| map.get(name).cloned())
+    }
+
+    /// Look up a variable that is not defined by the input.
+    fn fallback(&self, name: &str) -> Option<String> {
+        match self {
+            Interpolation::Disabled | Interpolation::File => None,
+            Interpolation::FileThenEnv => std::env::var(name).ok(),
+            Interpolation::Custom(lookup) => lookup(name),
+        }
+    }
+}
+
+impl fmt::Debug for Interpolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Interpolation::Disabled => f.write_str("Disabled"),
+            Interpolation::File => f.write_str("File"),
+            Interpolation::FileThenEnv => f.write_str("FileThenEnv"),
+            Interpolation::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// Parse an envfile and evaluate its values with the default [`Interpolation`].
+pub(crate) fn parse(input: &str, path: Option<&Path>) -> Result<Vec<Variable>> {
+    parse_with(input, path, &mut HashMap::new(), &Interpolation::default())
+}
+
+/// Parse an envfile and evaluate its values, additionally substituting
+/// variables `defined` by previously parsed inputs.
+///
+/// The variables of the input are added to `defined`.
+pub(crate) fn parse_with(
+    input: &str,
+    path: Option<&Path>,
+    defined: &mut HashMap<String, String>,
+    interpolation: &Interpolation,
+) -> Result<Vec<Variable>> {
+    let statements = scan(input).map_err(|e| e.into_error(input, path))?;
+
+    let mut variables = Vec::new();
+
+    for assignment in statements.into_iter().filter_map(|s| s.assignment) {
+        let name = &input[assignment.key.clone()];
+        let raw = &input[assignment.value.clone()];
+        let value = match interpolation {
+            Interpolation::Disabled => unquote_literal(raw),
+            interpolation => unquote(raw, |name| {
+                defined
+                    .get(name)
+                    .cloned()
+                    .or_else(|| interpolation.fallback(name))
+            }),
+        }
+        .map_err(|e| e.shift(assignment.value.start).into_error(input, path))?;
+
+        defined.insert(name.into(), value.clone());
+        variables.push(Variable {
+            name: name.into(),
+            value,
+            location: Location {
+                path: path.map(Into::into),
+                line: assignment.line,
+                column: assignment.column,
+            },
+        });
+    }
+
+    Ok(variables)
+}
+
+/// Split an envfile into statements without evaluating values.
+pub(crate) fn scan(input: &str) -> std::result::Result<Vec<Statement>, SyntaxError> {
+    let mut scanner = Scanner {
+        input,
+        pos: 0,
+        line: 1,
+        line_start: 0,
+    };
+    let mut statements = Vec::new();
+
+    if input.starts_with('\u{feff}') {
+        scanner.pos = '\u{feff}'.len_utf8();
+        scanner.line_start = scanner.pos;
+    }
+
+    while scanner.pos < input.len() {
+        let start = scanner.pos;
+        let assignment = scanner.statement()?;
+        statements.push(Statement {
+            span: start..scanner.pos,
+            assignment,
+        });
+    }
+
+    Ok(statements)
+}
+
+struct Scanner<'a> {
+    input: &'a str,
+    pos: usize,
+    line: usize,
+    line_start: usize,
+}
+
+impl Scanner<'_> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.line_start = self.pos;
+        }
+        Some(c)
+    }
+
+    fn column(&self) -> usize {
+        self.input[self.line_start..self.pos].chars().count() + 1
+    }
+
+    fn at_line_end(&self) -> bool {
+        let rest = &self.input[self.pos..];
+        rest.is_empty() || rest.starts_with('\n') || rest.starts_with("\r\n")
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    /// Skip the remainder of the line, including the line ending.
+    fn skip_line(&mut self) {
+        while let Some(c) = self.bump() {
+            if c == '\n' {
+                break;
+            }
+        }
+    }
+
+    fn error(&self, message: &'static str) -> SyntaxError {
+        SyntaxError::new(self.pos, message)
+    }
+
+    fn statement(&mut self) -> std::result::Result<Option<Assignment>, SyntaxError> {
+        self.skip_whitespace();
+        if self.at_line_end() || self.peek() == Some('#') {
+            self.skip_line();
+            return Ok(None);
+        }
+
+        let mut key = self.key()?;
+        self.skip_whitespace();
+
+        // `export` can be either an optional prefix or a key itself
+        if &self.input[key.clone()] == "export" && self.peek() != Some('=') {
+            key = self.key()?;
+            self.skip_whitespace();
+        }
+
+        if self.peek() != Some('=') {
+            return Err(self.error("expected '=' after the key"));
+        }
+        self.bump();
+        self.skip_whitespace();
+
+        let line = self.line;
+        let column = self.column();
+        let value = self.value()?;
+
+        self.skip_whitespace();
+        if !self.at_line_end() && self.peek() != Some('#') {
+            return Err(self.error("unexpected whitespace in unquoted value"));
+        }
+        self.skip_line();
+
+        Ok(Some(Assignment {
+            key,
+            value,
+            line,
+            column,
+        }))
+    }
+
+    fn key(&mut self) -> std::result::Result<Range<usize>, SyntaxError> {
+        let start = self.pos;
+        if !self.peek().is_some_and(is_key_start) {
+            return Err(self.error("expected a key starting with a letter or '_'"));
+        }
+        while self.peek().is_some_and(is_key_char) {
+            self.bump();
+        }
+        Ok(start..self.pos)
+    }
+
+    fn value(&mut self) -> std::result::Result<Range<usize>, SyntaxError> {
+        let start = self.pos;
+        if self.peek() == Some('#') {
+            return Ok(start..start);
+        }
+
+        loop {
+            match self.peek() {
+                None | Some(' ' | '\t') => break,
+                _ if self.at_line_end() => break,
+                Some('\'') => {
+                    let open = self.pos;
+                    self.bump();
+                    loop {
+                        match self.bump() {
+                            Some('\'') => break,
+                            Some(_) => {}
+                            None => {
+                                return Err(SyntaxError::new(open, "unterminated single quote"));
+                            }
+                        }
+                    }
+                }
+                Some('"') => {
+                    let open = self.pos;
+                    self.bump();
+                    loop {
+                        match self.bump() {
+                            Some('"') => break,
+                            Some('\\') => {
+                                self.bump();
+                            }
+                            Some(_) => {}
+                            None => {
+                                return Err(SyntaxError::new(open, "unterminated double quote"));
+                            }
+                        }
+                    }
+                }
+                // Defaults of substitutions may contain whitespace
+                Some('$') if self.input[self.pos..].starts_with("${") => {
+                    let open = self.pos;
+                    let mut depth = 0;
+                    loop {
+                        match self.bump() {
+                            Some('{') => depth += 1,
+                            Some('}') if depth == 1 => break,
+                            Some('}') => depth -= 1,
+                            Some(_) if !self.at_line_end() => {}
+                            _ => return Err(SyntaxError::new(open, "unterminated '${'")),
+                        }
+                    }
+                }
+                Some('\\') => {
+                    self.bump();
+                    if self.bump().is_none() {
+                        return Err(self.error("expected a character after '\\'"));
+                    }
+                }
+                Some(_) => {
+                    self.bump();
+                }
+            }
+        }
+
+        Ok(start..self.pos)
+    }
+}
+
+/// Evaluate a raw value by removing quotes, resolving escapes and substituting
+/// variables through `lookup`.
+///
+/// The offset of a returned error is relative to the start of `raw`.
+fn is_key_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+/// Fail with [`Error::InvalidKey`] unless `key` can be read back as a key.
+pub(crate) fn validate_key(key: &str) -> Result<()> {
+    let mut chars = key.chars();
+    match chars.next().is_some_and(is_key_start) && chars.all(is_key_char) {
+        true => Ok(()),
+        false => Err(Error::InvalidKey { key: key.into() }),
+    }
+}
+
+pub(crate) fn unquote<F>(raw: &str, mut lookup: F) -> std::result::Result<String, SyntaxError>
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    evaluate(raw, Some(&mut lookup))
+}
+
+/// Evaluate a raw value like [`unquote`] but keep references to variables as written.
+pub(crate) fn unquote_literal(raw: &str) -> std::result::Result<String, SyntaxError> {
+    evaluate::<fn(&str) -> Option<String>>(raw, None)
+}
+
+fn evaluate<F>(raw: &str, mut lookup: Option<&mut F>) -> std::result::Result<String, SyntaxError>
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut output = String::with_capacity(raw.len());
+    let mut chars = raw.char_indices().peekable();
+
+    let escape = |index: usize, c: Option<(usize, char)>| match c {
+        Some((_, c @ ('\\' | '\'' | '"' | '$' | ' '))) => Ok(c),
+        Some((_, 'n')) => Ok('\n'),
+        _ => Err(SyntaxError::new(index, "invalid escape sequence")),
+    };
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\'' => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                    output.push(c);
+                }
+            }
+            '"' => {
+                while let Some((index, c)) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => output.push(escape(index, chars.next())?),
+                        '$' => match lookup.as_deref_mut() {
+                            Some(lookup) => {
+                                substitute(raw, index, &mut chars, &mut output, lookup)?
+                            }
+                            None => output.push('$'),
+                        },
+                        c => output.push(c),
+                    }
+                }
+            }
+            '\\' => output.push(escape(index, chars.next())?),
+            '$' => match lookup.as_deref_mut() {
+                Some(lookup) => substitute(raw, index, &mut chars, &mut output, lookup)?,
+                None => output.push('$'),
+            },
+            c => output.push(c),
+        }
+    }
+
+    Ok(output)
+}
+
+/// Substitute the variable referenced at `index`, right after its `$`.
+///
+/// Besides `$NAME` and `${NAME}`, the operators of Docker Compose are
+/// supported: `${NAME:-default}` and `${NAME-default}` fall back to a default,
+/// `${NAME:+alternative}` and `${NAME+alternative}` replace a set variable,
+/// `${NAME:?message}` and `${NAME?message}` require a variable and `$$`
+/// escapes a `$`. With a colon, empty variables are treated like unset ones.
+fn substitute<F>(
+    raw: &str,
+    index: usize,
+    chars: &mut std::iter::Peekable<std::str::CharIndices<'_>>,
+    output: &mut String,
+    lookup: &mut F,
+) -> std::result::Result<(), SyntaxError>
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut name = String::new();
+
+    if chars.next_if(|&(_, c)| c == '$').is_some() {
+        output.push('$');
+        return Ok(());
+    }
+
+    if chars.next_if(|&(_, c)| c == '{').is_none() {
+        while let Some((_, c)) = chars.next_if(|&(_, c)| is_name(c)) {
+            name.push(c);
+        }
+
+        match name.is_empty() {
+            true => output.push('$'),
+            false => output.push_str(&lookup(&name).unwrap_or_default()),
+        }
+        return Ok(());
+    }
+
+    while let Some((_, c)) = chars.next_if(|&(_, c)| is_name(c)) {
+        name.push(c);
+    }
+    if name.is_empty() {
+        return Err(SyntaxError::new(
+            index,
+            "expected a variable name after '${'",
+        ));
+    }
+
+    let colon = chars.next_if(|&(_, c)| c == ':').is_some();
+    let operator = match chars.next() {
+        Some((_, '}')) if !colon => {
+            output.push_str(&lookup(&name).unwrap_or_default());
+            return Ok(());
+        }
+        Some((_, operator @ ('-' | '+' | '?'))) => operator,
+        Some(_) => return Err(SyntaxError::new(index, "invalid substitution operator")),
+        None => return Err(SyntaxError::new(index, "unterminated '${'")),
+    };
+
+    // The word after the operator may contain nested substitutions, which are
+    // only evaluated if the word is used.
+    let mut depth = 0;
+    let mut word = None;
+    let mut start = None;
+    for (position, c) in chars.by_ref() {
+        let start = *start.get_or_insert(position);
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            '}' => {
+                word = Some((start, position));
+                break;
+            }
+            _ => {}
+        }
+    }
+    let Some((start, end)) = word else {
+        return Err(SyntaxError::new(index, "unterminated '${'"));
+    };
+    let word = |lookup: &mut F| interpolate(&raw[start..end], lookup).map_err(|e| e.shift(start));
+
+    let value = lookup(&name).filter(|value| !colon || !value.is_empty());
+    match (operator, value) {
+        ('-' | '?', Some(value)) => output.push_str(&value),
+        ('-', None) | ('+', Some(_)) => output.push_str(&word(lookup)?),
+        ('+', None) => {}
+        _ => return Err(SyntaxError::unset(index, name, word(lookup)?)),
+    }
+
+    Ok(())
+}
+
+/// Substitute the variables of `text` that is not enclosed in quotes.
+fn interpolate<F>(text: &str, lookup: &mut F) -> std::result::Result<String, SyntaxError>
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '$' => substitute(text, index, &mut chars, &mut output, lookup)?,
+            c => output.push(c),
+        }
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{Interpolation, parse, parse_with, scan};
+    use crate::Error;
+
+    #[test]
+    fn parse_values() {
+        //* Given
+        let input = r#"
+# comment
+KEY=1
+KEY2="2"
+KEY3='3'
+KEY4='fo ur'
+KEY5="fi ve"
+KEY6=s\ ix
+KEY7=
+KEY8=
+KEY9=   # foo
+KEY10  ="whitespace before ="
+KEY11=    "whitespace after ="
+export="export as key"
+export   SHELL_LOVER=1
+KEY12="line 1
+line 2"
+KEY13=value#with#hash
+KEY14='sweet $\fgs'\''fds'
+KEY15="${KEY}/$KEY2"
+"#;
+
+        //* When
+        let variables = parse(input, None).expect("Failed to parse");
+
+        //* Then
+        let parsed: Vec<(&str, &str)> = variables
+            .iter()
+            .map(|v| (v.name.as_str(), v.value.as_str()))
+            .collect();
+        assert_eq!(
+            parsed,
+            vec![
+                ("KEY", "1"),
+                ("KEY2", "2"),
+                ("KEY3", "3"),
+                ("KEY4", "fo ur"),
+                ("KEY5", "fi ve"),
+                ("KEY6", "s ix"),
+                ("KEY7", ""),
+                ("KEY8", ""),
+                ("KEY9", ""),
+                ("KEY10", "whitespace before ="),
+                ("KEY11", "whitespace after ="),
+                ("export", "export as key"),
+                ("SHELL_LOVER", "1"),
+                ("KEY12", "line 1\nline 2"),
+                ("KEY13", "value#with#hash"),
+                ("KEY14", r"sweet $\fgs'fds"),
+                ("KEY15", "1/2"),
+            ]
+        );
+        assert_eq!(variables[1].location.line, 4);
+        assert_eq!(variables[1].location.column, 6);
+    }
+
+    #[test]
+    fn parse_errors_carry_location() {
+        //* Given
+        let input = "KEY=1\nvery bacon = yes\n";
+
+        //* When
+        let error = parse(input, None).err().expect("Expected a syntax error");
+
+        //* Then
+        match error {
+            Error::Syntax { location, .. } => {
+                assert_eq!(location.line, 2);
+                assert_eq!(location.column, 6);
+            }
+            e => panic!("Unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn scan_is_lossless() {
+        //* Given
+        let input = "\u{feff}# comment\r\n\nexport KEY=\"multi\nline\" # note\nOTHER=1";
+
+        //* When
+        let statements = scan(input).expect("Failed to scan");
+
+        //* Then
+        let start = '\u{feff}'.len_utf8();
+        let text: String = statements.iter().map(|s| &input[s.span.clone()]).collect();
+        assert_eq!(text, input[start..]);
+        assert_eq!(statements.len(), 4);
+        assert!(statements[3].assignment.is_some());
+    }
+
+    #[test]
+    fn parse_with_interpolation_modes() {
+        //* Given
+        let input = "A=1\nB=\"$A-${PATH}-$CUSTOM\"\nC='$A'";
+        let path = std::env::var("PATH").unwrap();
+
+        let evaluate = |interpolation: Interpolation| {
+            let variables = parse_with(input, None, &mut HashMap::new(), &interpolation)
+                .expect("Failed to parse");
+            (variables[1].value.clone(), variables[2].value.clone())
+        };
+
+        //* When
+        let disabled = evaluate(Interpolation::Disabled);
+        let file = evaluate(Interpolation::File);
+        let env = evaluate(Interpolation::FileThenEnv);
+        let custom = evaluate(Interpolation::map([("A", "2"), ("CUSTOM", "3")]));
+
+        //* Then
+        assert_eq!(disabled, ("$A-${PATH}-$CUSTOM".into(), "$A".into()));
+        assert_eq!(file, ("1--".into(), "$A".into()));
+        assert_eq!(env, (format!("1-{path}-"), "$A".into()));
+        assert_eq!(custom, ("1--3".into(), "$A".into()));
+    }
+
+    #[test]
+    fn parse_compose_operators() {
+        //* Given
+        let input = r#"
+SET=value
+EMPTY=
+A=${UNSET:-default}-${EMPTY:-default}-${EMPTY-default}-${SET:-default}
+B=${UNSET-$SET}-${SET:+alt}-${EMPTY:+alt}-${EMPTY+alt}-${UNSET+alt}
+C="${SET:?required}-$${SET}-${UNSET:-${SET:-nested}}"
+D=${SET:-${UNSET:?not evaluated}}
+"#;
+
+        //* When
+        let variables = parse_with(input, None, &mut HashMap::new(), &Interpolation::File)
+            .expect("Failed to parse");
+
+        //* Then
+        let values: Vec<&str> = variables[2..].iter().map(|v| v.value.as_str()).collect();
+        assert_eq!(
+            values,
+            vec![
+                "default-default--value",
+                "value-alt--alt-",
+                "value-${SET}-value",
+                "value",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_required_variable() {
+        //* Given
+        let inputs = [
+            ("KEY=1\nURL=${HOST:?host is required}", "host is required"),
+            ("HOST=\nURL=\"x${HOST:?}\"", ""),
+            (
+                "URL=${HOST?${MISSING:-fallback} message}",
+                "fallback message",
+            ),
+        ];
+
+        for (input, expected) in inputs {
+            //* When
+            let error = parse_with(input, None, &mut HashMap::new(), &Interpolation::File)
+                .err()
+                .expect("Expected an unset variable");
+
+            //* Then
+            match error {
+                Error::UnsetVariable {
+                    name,
+                    message,
+                    location,
+                } => {
+                    assert_eq!(name, "HOST");
+                    assert_eq!(message, expected);
+                    assert_eq!(location.line, input.matches('\n').count() + 1);
+                }
+                e => panic!("Unexpected error: {e}"),
+            }
+        }
+
+        let error = parse("URL=${HOST%x}", None)
+            .err()
+            .expect("Expected a syntax error");
+        assert!(matches!(error, Error::Syntax { .. }));
+    }
+}
@@ -1,9 +1,15 @@
 use std::path::Path;
 
 use super::{
-    de::{from_env_inner, from_file_inner, from_reader_inner, from_str_inner},
+    de::{
+        from_env_inner, from_env_with_report_inner, from_file_inner, from_file_with_report_inner,
+        from_reader_inner, from_reader_with_report_inner, from_str_inner,
+        from_str_with_report_inner,
+    },
     error::Result,
-    ser::{to_file_inner, to_string_inner, to_writer_inner},
+    loader::Loader,
+    parse::Interpolation,
+    ser::{KeyCase, to_file_inner, to_string_inner, to_writer_inner},
 };
 
 /// Instantiates [`Prefixed`] from which values can be both serialized and deserialized with a prefix.
@@ -64,29 +70,82 @@ use super::{
 ///     Ok(())
 /// }
 /// ```
+///
+/// ## Separating nested keys
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use serde_envfile::{prefixed, Error};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Database {
+///     url: String,
+/// }
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Config {
+///     database: Database,
+///     database_url: String,
+/// }
+///
+/// fn main() -> Result<(), Error> {
+///     let config = Config {
+///         database: Database { url: "postgres://db.internal".into() },
+///         database_url: "postgres://localhost".into(),
+///     };
+///
+///     let env_string = prefixed("APP__").separator("__").to_string(&config)?;
+///     assert_eq!(
+///         env_string,
+///         "APP__DATABASE__URL=\"postgres://db.internal\"\nAPP__DATABASE_URL=\"postgres://localhost\""
+///     );
+///
+///     let parsed: Config = prefixed("APP__").separator("__").from_str(&env_string)?;
+///     assert_eq!(parsed, config);
+///
+///     Ok(())
+/// }
+/// ```
 pub fn prefixed<'a>(prefix: &'a str) -> Prefixed<'a> {
-    Prefixed(prefix)
+    Prefixed {
+        prefix,
+        separator: "_",
+        key_case: KeyCase::default(),
+        interpolation: Interpolation::default(),
+    }
 }
 
 /// Helper structure to work with prefixed environment variables more efficiently.
 ///
 /// This struct provides methods for serializing and deserializing data with a consistent prefix.
 /// Use the [`prefixed`] function to create an instance of this struct.
-pub struct Prefixed<'a>(&'a str);
+pub struct Prefixed<'a> {
+    pub(crate) prefix: &'a str,
+    pub(crate) separator: &'a str,
+    pub(crate) key_case: KeyCase,
+    pub(crate) interpolation: Interpolation,
+}
 
 impl<'a> Prefixed<'a> {
+    /// Set the separator joining the keys of nested structures, in both
+    /// directions. Defaults to `_`.
+    pub fn separator(mut self, separator: &'a str) -> Self {
+        self.separator = separator;
+        self
+    }
+
     pub fn from_env<T>(&self) -> Result<T>
     where
         T: serde::de::DeserializeOwned,
     {
-        from_env_inner::<T>(Some(self.0))
+        from_env_inner::<T>(Some(self))
     }
 
     pub fn from_str<T>(&self, input: &'a str) -> Result<T>
     where
         T: serde::de::DeserializeOwned,
     {
-        from_str_inner::<T>(Some(self.0), input)
+        from_str_inner::<T>(Some(self), input)
     }
 
     pub fn from_reader<R, T>(&self, reader: R) -> Result<T>
@@ -94,21 +153,81 @@ impl<'a> Prefixed<'a> {
         R: std::io::Read,
         T: serde::de::DeserializeOwned,
     {
-        from_reader_inner::<R, T>(Some(self.0), reader)
+        from_reader_inner::<R, T>(Some(self), reader)
     }
 
     pub fn from_file<T>(&self, path: &Path) -> Result<T>
     where
         T: serde::de::DeserializeOwned,
     {
-        from_file_inner::<T>(Some(self.0), path)
+        from_file_inner::<T>(Some(self), path)
+    }
+
+    /// Like [`from_env`](Self::from_env), additionally returning the prefixed
+    /// keys that no field of `T` consumed.
+    pub fn from_env_with_report<T>(&self) -> Result<(T, Vec<String>)>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        from_env_with_report_inner::<T>(Some(self))
+    }
+
+    /// Like [`from_str`](Self::from_str), additionally returning the prefixed
+    /// keys that no field of `T` consumed.
+    pub fn from_str_with_report<T>(&self, input: &'a str) -> Result<(T, Vec<String>)>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        from_str_with_report_inner::<T>(Some(self), input)
+    }
+
+    /// Like [`from_reader`](Self::from_reader), additionally returning the
+    /// prefixed keys that no field of `T` consumed.
+    pub fn from_reader_with_report<R, T>(&self, reader: R) -> Result<(T, Vec<String>)>
+    where
+        R: std::io::Read,
+        T: serde::de::DeserializeOwned,
+    {
+        from_reader_with_report_inner::<R, T>(Some(self), reader)
+    }
+
+    /// Like [`from_file`](Self::from_file), additionally returning the prefixed
+    /// keys that no field of `T` consumed.
+    pub fn from_file_with_report<T>(&self, path: &Path) -> Result<(T, Vec<String>)>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        from_file_with_report_inner::<T>(Some(self), path)
+    }
+
+    /// Set the case applied to keys and the prefix, in both directions.
+    /// Defaults to [`KeyCase::Upper`].
+    pub fn key_case(mut self, key_case: KeyCase) -> Self {
+        self.key_case = key_case;
+        self
+    }
+
+    /// Set how variables referenced in envfiles are substituted when
+    /// deserializing. Defaults to [`Interpolation::FileThenEnv`].
+    pub fn interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    /// Create a [`Loader`] for layered sources that expects the prefix.
+    pub fn loader(&self) -> Loader {
+        Loader::new()
+            .prefix(self.prefix)
+            .separator(self.separator)
+            .key_case(self.key_case)
+            .interpolation(self.interpolation.clone())
     }
 
     pub fn to_string<T>(&self, v: &T) -> Result<String>
     where
         T: serde::ser::Serialize,
     {
-        to_string_inner(Some(self.0), v)
+        to_string_inner(Some(self), v)
     }
 
     pub fn to_writer<W, T>(&self, writer: W, v: &T) -> Result<()>
@@ -116,7 +235,7 @@ impl<'a> Prefixed<'a> {
         W: std::io::Write,
         T: serde::ser::Serialize,
     {
-        to_writer_inner(Some(self.0), writer, v)
+        to_writer_inner(Some(self), writer, v)
     }
 
     pub fn to_file<P, T>(&self, path: P, v: &T) -> Result<()>
@@ -124,7 +243,7 @@ impl<'a> Prefixed<'a> {
         P: AsRef<Path>,
         T: serde::ser::Serialize,
     {
-        to_file_inner(Some(self.0), path, v)
+        to_file_inner(Some(self), path, v)
     }
 }
 
@@ -133,7 +252,7 @@ mod tests {
     use std::io::Cursor;
 
     use super::prefixed;
-    use crate::Value;
+    use crate::{Error, Interpolation, KeyCase, Value};
 
     #[test]
     fn serialize_to_string_with_prefix() {
@@ -213,4 +332,105 @@ mod tests {
         };
         assert_eq!(output, expected_output);
     }
+
+    #[test]
+    fn deserialize_with_report_with_prefix() {
+        //* Given
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Config {
+            hello: String,
+        }
+
+        let env = "SERDE_ENVFILE_HELLO=world\nSERDE_ENVFILE_HELO=typo\nOTHER=1";
+
+        //* When
+        let (output, unused) = prefixed("serde_envfile_")
+            .from_str_with_report::<Config>(env)
+            .expect("Failed to deserialize");
+
+        //* Then
+        assert_eq!(output.hello, "world");
+        assert_eq!(unused, vec!["SERDE_ENVFILE_HELO"]);
+    }
+
+    #[test]
+    fn aggregate_errors_with_prefix() {
+        //* Given
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Config {
+            host: String,
+            port: u16,
+            user: String,
+        }
+
+        let env = "APP_PORT=eighty\nHOST=unprefixed";
+
+        //* When
+        let error = prefixed("app_")
+            .from_str::<Config>(env)
+            .expect_err("Expected errors");
+
+        //* Then
+        let keys: Vec<String> = match error {
+            Error::Aggregate { errors } => errors
+                .into_iter()
+                .map(|error| match error {
+                    Error::MissingKey { key } | Error::InvalidValue { key, .. } => key,
+                    e => panic!("Unexpected error: {e}"),
+                })
+                .collect(),
+            e => panic!("Unexpected error: {e}"),
+        };
+        assert_eq!(keys, vec!["APP_HOST", "APP_USER", "APP_PORT"]);
+    }
+
+    #[test]
+    fn key_case_with_prefix() {
+        //* Given
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Config {
+            database_url: String,
+        }
+
+        let config = Config {
+            database_url: "postgres://localhost".into(),
+        };
+        let prefixed = || prefixed("myApp_").key_case(KeyCase::ScreamingSnake);
+
+        //* When
+        let output = prefixed().to_string(&config).expect("Failed to serialize");
+        let parsed = prefixed()
+            .from_str::<Config>(&output)
+            .expect("Failed to deserialize");
+        let missing = prefixed()
+            .from_str::<Config>("MYAPP_DATABASE_URL=x")
+            .expect_err("Expected a missing key");
+
+        //* Then
+        assert_eq!(output, "MY_APP_DATABASE_URL=\"postgres://localhost\"");
+        assert_eq!(parsed, config);
+        assert!(matches!(missing, Error::MissingKey { key } if key == "MY_APP_DATABASE_URL"));
+    }
+
+    #[test]
+    fn interpolation_with_prefix() {
+        //* Given
+        let env = "APP_HOST=localhost\nAPP_URL=http://${APP_HOST}";
+
+        //* When
+        let value: Value = prefixed("APP_")
+            .interpolation(Interpolation::Disabled)
+            .from_str(env)
+            .expect("Failed to deserialize");
+        let (interpolated, _) = prefixed("APP_")
+            .interpolation(Interpolation::map([("APP_PORT", "80")]))
+            .from_str_with_report::<Value>("APP_URL=http://${APP_HOST:-host}:$APP_PORT")
+            .expect("Failed to deserialize");
+
+        //* Then
+        assert_eq!(value.get("url").unwrap(), "http://${APP_HOST}");
+        assert_eq!(interpolated.get("url").unwrap(), "http://host:80");
+    }
 }
@@ -0,0 +1,121 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use super::error::Location;
+
+/// Where the value of a variable was defined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// An envfile or string input. The path of the location is `None` for
+    /// input that was not read from a file.
+    Input(Location),
+    /// The environment of the application.
+    Env,
+    /// Key-value pairs provided by the caller.
+    Vars,
+}
+
+impl Origin {
+    /// Returns the location of the definition if it originates from an input.
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            Origin::Input(location) => Some(location),
+            Origin::Env | Origin::Vars => None,
+        }
+    }
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Input(location) if location.path.is_some() => write!(f, "{location}"),
+            Origin::Input(location) => write!(f, "input at {location}"),
+            Origin::Env => write!(f, "environment"),
+            Origin::Vars => write!(f, "provided variables"),
+        }
+    }
+}
+
+/// A single definition of a variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Definition {
+    /// Name of the variable as written in its source.
+    pub name: String,
+    pub value: String,
+    pub origin: Origin,
+}
+
+/// Records the origin of every variable of a [`Loader`](crate::Loader).
+///
+/// Keys are looked up case-insensitively, including a prefix if one is set.
+///
+/// # Example
+///
+/// ```
+/// use serde_envfile::{Error, Loader, Origin, Value};
+///
+/// fn provenance_example() -> Result<(), Error> {
+///     let (value, provenance) = Loader::new()
+///         .str("DATABASE_URL=postgres://localhost")
+///         .vars([("DATABASE_URL", "postgres://db.internal")])
+///         .load_with_provenance::<Value>()?;
+///
+///     assert_eq!(value.get("database_url").unwrap(), "postgres://db.internal");
+///     assert_eq!(provenance.origin("DATABASE_URL"), Some(&Origin::Vars));
+///     assert_eq!(provenance.overridden("DATABASE_URL").len(), 1);
+///
+///     Ok(())
+/// }
+/// # provenance_example().unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+    /// Definitions of each variable in the order they were applied.
+    definitions: Vec<Vec<Definition>>,
+    positions: HashMap<String, usize>,
+}
+
+impl Provenance {
+    pub(crate) fn push(&mut self, definition: Definition) {
+        let key = definition.name.to_lowercase();
+        match self.positions.get(&key) {
+            Some(&position) => self.definitions[position].push(definition),
+            None => {
+                self.positions.insert(key, self.definitions.len());
+                self.definitions.push(vec![definition]);
+            }
+        }
+    }
+
+    fn definitions(&self, key: &str) -> &[Definition] {
+        match self.positions.get(&key.to_lowercase()) {
+            Some(&position) => &self.definitions[position],
+            None => &[],
+        }
+    }
+
+    /// Returns the definition of `key` that is in effect.
+    pub fn get(&self, key: &str) -> Option<&Definition> {
+        self.definitions(key).last()
+    }
+
+    /// Returns the origin of the definition of `key` that is in effect.
+    pub fn origin(&self, key: &str) -> Option<&Origin> {
+        self.get(key).map(|definition| &definition.origin)
+    }
+
+    /// Returns the earlier definitions of `key` that were overridden, oldest first.
+    pub fn overridden(&self, key: &str) -> &[Definition] {
+        let definitions = self.definitions(key);
+        &definitions[..definitions.len().saturating_sub(1)]
+    }
+
+    /// Iterate over the definitions in effect, in the order variables were first defined.
+    pub fn iter(&self) -> impl Iterator<Item = &Definition> {
+        self.definitions
+            .iter()
+            .filter_map(|definitions| definitions.last())
+    }
+}
@@ -10,6 +10,11 @@ cfg_if::cfg_if! {
 
 /// Flexible representation of environment variables.
 ///
+/// Keys are lowercased during deserialization, unless the [`Deserializer`]
+/// is [case-sensitive](crate::Deserializer::case_sensitive).
+///
+/// [`Deserializer`]: crate::Deserializer
+///
 /// # Example
 ///
 /// ```
@@ -82,8 +87,10 @@ where
 
 #[cfg(test)]
 mod tests {
+    use serde::{Deserialize as _, Serialize as _};
+
     use super::Value;
-    use crate::{de::from_str, ser::to_string};
+    use crate::{Deserializer, KeyCase, Serializer, de::from_str, ser::to_string};
 
     #[test]
     fn value_to_string() {
@@ -113,4 +120,43 @@ mod tests {
         let expected_deserialized = Value::from_iter([("key1", "VALUE1"), ("key2", "VALUE2")]);
         assert_eq!(value_deserialized, expected_deserialized);
     }
+
+    #[test]
+    fn value_preserves_case() {
+        //* Given
+        let input = "apiKey=secret\nAPI_KEY=other\nMixed_Case=value";
+
+        //* When
+        let mut deserializer = Deserializer::from_str(input)
+            .expect("Failed to parse")
+            .case_sensitive(true);
+        let value = Value::deserialize(&mut deserializer).expect("Failed to deserialize Value");
+
+        let mut serializer = Serializer::builder()
+            .key_case(KeyCase::Preserve)
+            .build(Vec::new());
+        value
+            .serialize(&mut serializer)
+            .expect("Failed to serialize Value");
+        let output = String::from_utf8(serializer.into_inner()).expect("Invalid UTF-8 sequence");
+
+        //* Then
+        let expected = Value::from_iter([
+            ("apiKey", "secret"),
+            ("API_KEY", "other"),
+            ("Mixed_Case", "value"),
+        ]);
+        assert_eq!(value, expected);
+
+        let mut lines: Vec<&str> = output.lines().collect();
+        lines.sort();
+        assert_eq!(
+            lines,
+            vec![
+                "API_KEY=\"other\"",
+                "Mixed_Case=\"value\"",
+                "apiKey=\"secret\""
+            ]
+        );
+    }
 }